@@ -3,6 +3,10 @@ MultiboxMovement.MapSizes = {}
 
 local ROTATION_FUDGE_FACTOR = 0.2 -- Radians
 local MOVEMENT_FUDGE_FACTOR = 0.5 -- Yards
+-- The tool steers precisely with the analog heading error and distance, so a target is only
+-- reached within these. Keep in sync with HEADING_TOLERANCE/DISTANCE_TOLERANCE in src/movement.rs
+local ANALOG_ROTATION_TOLERANCE = 0.035 -- Radians
+local ANALOG_MOVEMENT_TOLERANCE = 0.25 -- Yards
 
 
 function MultiboxMovement:RegisterMapSize(zone, ...)
@@ -24,8 +28,13 @@ function MultiboxMovement:GetMapSize()
     return dims[1], dims[2]
 end
 
+-- Returns the movement/rotation bitmask, the signed heading error (radians, positive = turn left),
+-- the signed distance along the facing (yards, positive = ahead) and whether the target is reached
 function MultiboxMovement:getMovementRotationBitmask(targetRotation, targetX, targetY)
     local bitmask = 0
+    local headingError = nil
+    local distance = nil
+    local done = true
     local currentFacing = GetPlayerFacing()
     if targetRotation then
         -- ROTATION
@@ -42,10 +51,10 @@ function MultiboxMovement:getMovementRotationBitmask(targetRotation, targetX, ta
         elseif rotationDifference > ROTATION_FUDGE_FACTOR then
             bitmask = bitmask + 2 -- Rotate left (bit 1)
         end
-        
-        if (bitmask == 0) then
-            -- Reached target, clear rotation
-            targetRotation = nil
+
+        headingError = rotationDifference
+        if math.abs(rotationDifference) > ANALOG_ROTATION_TOLERANCE then
+            done = false
         end
     end
     if targetX and targetY then
@@ -87,12 +96,12 @@ function MultiboxMovement:getMovementRotationBitmask(targetRotation, targetX, ta
             bitmask = bitmask + 32 -- Move left (bit 5)
         end
 
-        if (bitmask <= 2) then
-            -- Only has rotation bits set, clear movement
-            targetRotation = nil
+        distance = relativeY
+        if math.abs(relativeY) > ANALOG_MOVEMENT_TOLERANCE or math.abs(relativeX) > MOVEMENT_FUDGE_FACTOR then
+            done = false
         end
     end
-    return bitmask
+    return bitmask, headingError, distance, done
 end
 
 
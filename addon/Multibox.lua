@@ -37,13 +37,39 @@ local function drawPixel(r, g, b)
     commandTexture:SetVertexColor(r, g, b)
 end
 
+-- Steps of the analog values in the movement pixel, must match src/protocol.rs
+local HEADING_STEP = math.pi / 127 -- Radians
+local DISTANCE_STEP = 0.25 -- Yards
+
+-- Encode a signed value as a color byte: 0 = not set, 1-255 = -127..127 steps
+local function encodeSigned(value, step)
+    if value == nil then
+        return 0
+    end
+    local steps = math.floor(value / step + 0.5)
+    steps = math.max(-127, math.min(127, steps))
+    return steps + 128
+end
+
+-- Flipped every HEARTBEAT_INTERVAL seconds and drawn as the top bit of the movement pixel's
+-- red channel, so the tool can tell a frozen addon from an idle one
+local HEARTBEAT_INTERVAL = 0.25
+local heartbeat = false
+local heartbeatElapsed = 0
+
 -- New function to draw the movement/rotation pixel
-local function drawMovementRotationPixel(value)
+-- Red is the bitmask and heartbeat, green the heading error and blue the distance to the target
+local function drawMovementRotationPixel(value, headingError, distance)
     if value == nil then
         value = 0
     end
-    -- Only using the red component for simplicity, as it's a single byte bitmask
-    movementRotationTexture:SetVertexColor(value / 255, 0, 0)
+    if heartbeat then
+        value = value + 128
+    end
+    movementRotationTexture:SetVertexColor(
+        value / 255,
+        encodeSigned(headingError, HEADING_STEP) / 255,
+        encodeSigned(distance, DISTANCE_STEP) / 255)
 end
 
 function Multibox:OnInitialize()
@@ -52,10 +78,51 @@ function Multibox:OnInitialize()
     self.stackingEnabled = false
     self.stackedPlayers = {}
     self.controlCommand = 0
+    self.controlCommandAge = 0
     self:RegisterComm(MESSAGE_PREFIX)
     self:Init()
 end
 
+-- Keys the tool taps to send feedback, must match Feedback::key in src/protocol.rs
+local FEEDBACK_KEYS = {
+    NUMPADDIVIDE = "executed",     -- the control command on screen was carried out
+    NUMPADDECIMAL = "failed",      -- the control command on screen could not be carried out
+    NUMPADMULTIPLY = "broadcast_on",
+    NUMPADMINUS = "broadcast_off",
+    NUMPADPLUS = "alive",          -- sent every 5 seconds while the tool runs
+}
+-- Give up on a control command the tool has not acknowledged by then (not running?)
+local CONTROL_ACK_TIMEOUT = 2
+-- Warn when the tool has not been heard from for this long
+local TOOL_ALIVE_TIMEOUT = 15
+
+-- Show a control command to the tool until it is acknowledged
+function Multibox:SetControlCommand(command)
+    self.controlCommand = command
+    self.controlCommandAge = 0
+end
+
+function Multibox:OnFeedback(feedback)
+    self.toolLastSeen = GetTime()
+    if self.toolLost then
+        self.toolLost = false
+        DEFAULT_CHAT_FRAME:AddMessage("MultiboxSoftware is responding again")
+    end
+
+    if feedback == "executed" then
+        self.controlCommand = 0
+    elseif feedback == "failed" then
+        DEFAULT_CHAT_FRAME:AddMessage("MultiboxSoftware could not run control command " .. self.controlCommand)
+        self.controlCommand = 0
+    elseif feedback == "broadcast_on" or feedback == "broadcast_off" then
+        local enabled = (feedback == "broadcast_on")
+        if self.broadcastEnabled ~= enabled then
+            self.broadcastEnabled = enabled
+            DEFAULT_CHAT_FRAME:AddMessage("Broadcast " .. (enabled and "enabled" or "disabled") .. " by MultiboxSoftware")
+        end
+    end
+end
+
 -- OnUpdate loop to draw the key value as a pixel color
 local timeElapsed = 0
 local stackBroadcastTimeElapsed = 0
@@ -72,12 +139,31 @@ end
 frame:HookScript("OnUpdate", function(self, elapsed)
     timeElapsed = timeElapsed + elapsed
     stackBroadcastTimeElapsed = stackBroadcastTimeElapsed + elapsed
+    heartbeatElapsed = heartbeatElapsed + elapsed
+    if heartbeatElapsed > HEARTBEAT_INTERVAL then
+        heartbeatElapsed = 0
+        heartbeat = not heartbeat
+    end
+
+    if Multibox.controlCommand ~= 0 then
+        Multibox.controlCommandAge = Multibox.controlCommandAge + elapsed
+        if Multibox.controlCommandAge > CONTROL_ACK_TIMEOUT then
+            DEFAULT_CHAT_FRAME:AddMessage("Control command " .. Multibox.controlCommand .. " was not acknowledged")
+            Multibox.controlCommand = 0
+        end
+    end
+
+    if Multibox.toolLastSeen and not Multibox.toolLost and GetTime() - Multibox.toolLastSeen > TOOL_ALIVE_TIMEOUT then
+        Multibox.toolLost = true
+        DEFAULT_CHAT_FRAME:AddMessage("MultiboxSoftware stopped responding")
+    end
 
     if (timeElapsed > 0.1) then
         timeElapsed = 0
         if not Multibox.keysEnabled then
             DEFAULT_CHAT_FRAME:AddMessage("Keys disabled, not sending commands")
-            drawPixel(0, 0, 0)
+            -- Control commands still go through
+            drawPixel(0, 0, Multibox.controlCommand / 255)
             drawMovementRotationPixel(0) -- Also clear movement pixel
             return
         end
@@ -88,9 +174,6 @@ frame:HookScript("OnUpdate", function(self, elapsed)
             local g = target / 255
             local b = Multibox.controlCommand / 255
             drawPixel(r, g, b)
-            if Multibox.controlCommand ~= 0 then
-                Multibox.controlCommand = 0
-            end
         end
     end
 
@@ -99,13 +182,15 @@ frame:HookScript("OnUpdate", function(self, elapsed)
         sendStackMessage()
     end
 
-    local movementRotationBitmask = MultiboxMovement:getMovementRotationBitmask(targetRotation, targetX, targetY)
-    if movementRotationBitmask == 0 then
+    local movementRotationBitmask, headingError, distance, done = MultiboxMovement:getMovementRotationBitmask(targetRotation, targetX, targetY)
+    if done then
         targetRotation = nil
         targetX = nil
         targetY = nil
+        drawMovementRotationPixel(0)
+    else
+        drawMovementRotationPixel(movementRotationBitmask, headingError, distance)
     end
-    drawMovementRotationPixel(movementRotationBitmask) -- Draw the movement/rotation bitmask
 end)
 
 -- Helper: Initialize keybinds to target raid members (1-40) using Numpad keys and modifiers
@@ -145,8 +230,23 @@ local function initTargettingKeybinds()
     end
 end
 
+-- Helper: Bind the keys the tool taps to send feedback
+local function initFeedbackKeybinds()
+    for key, feedback in pairs(FEEDBACK_KEYS) do
+        local buttonName = "MultiboxFeedbackButton_" .. key
+        if not _G[buttonName] then
+            local button = CreateFrame("Button", buttonName, UIParent)
+            button:SetScript("OnClick", function()
+                Multibox:OnFeedback(feedback)
+            end)
+        end
+        SetBindingClick(key, buttonName)
+    end
+end
+
 function Multibox:Init()
     initTargettingKeybinds();
+    initFeedbackKeybinds()
     MultiboxLFG:Initialize()
     MultiboxGuildBank:Initialize()
     MultiboxFollow:Initialize()
@@ -244,21 +344,38 @@ function Multibox:MboxCommandHandler(msg)
     elseif cmd == "broadcast" then
         self.broadcastEnabled = not self.broadcastEnabled
         if self.broadcastEnabled then
-            self.controlCommand = 3 -- enable broadcast
+            self:SetControlCommand(3) -- enable broadcast
             DEFAULT_CHAT_FRAME:AddMessage("Broadcast enabled")
         else
-            self.controlCommand = 4 -- disable broadcast
+            self:SetControlCommand(4) -- disable broadcast
             DEFAULT_CHAT_FRAME:AddMessage("Broadcast disabled")
         end
         self:SendBroadcast()
     elseif cmd == "swap" then
         local target = tonumber(args)
-        if target and target > 0 then
-            self.controlCommand = target + 4
+        if target and target > 0 and target <= 95 then
+            self:SetControlCommand(target + 4)
             DEFAULT_CHAT_FRAME:AddMessage("Signaling swap with window " .. target)
         else
             DEFAULT_CHAT_FRAME:AddMessage("Invalid swap target. Usage: /mbox swap <window_number>")
         end
+    elseif cmd == "focus" then
+        local target_str, mode = args:match("^(%S*)%s*(%S*)$")
+        local target = tonumber(target_str)
+        if target and target > 0 and target <= 50 then
+            if mode == "main" then
+                self:SetControlCommand(target + 149) -- focus and swap into the main position
+                DEFAULT_CHAT_FRAME:AddMessage("Signaling focus of window " .. target .. " as main")
+            else
+                self:SetControlCommand(target + 99)
+                DEFAULT_CHAT_FRAME:AddMessage("Signaling focus of window " .. target)
+            end
+        else
+            DEFAULT_CHAT_FRAME:AddMessage("Invalid focus target. Usage: /mbox focus <window_number> [main]")
+        end
+    elseif cmd == "release" then
+        self:SetControlCommand(200) -- release all keys held by the tool
+        DEFAULT_CHAT_FRAME:AddMessage("Signaling release of all held keys")
     elseif cmd == "follow" then
         MultiboxFollow.SetFollowTarget(args)
     elseif cmd == "move" then
@@ -267,13 +384,15 @@ function Multibox:MboxCommandHandler(msg)
         targetX = tonumber(x_str)
         targetY = tonumber(y_str)
         DEFAULT_CHAT_FRAME:AddMessage(string.format("Received move command: rotation=%.2f, x=%.4f, y=%.4f", targetRotation, targetX, targetY))
-        local movementRotationBitmask = MultiboxMovement:getMovementRotationBitmask(targetRotation, targetX, targetY)
-        if movementRotationBitmask == 0 then
+        local movementRotationBitmask, headingError, distance, done = MultiboxMovement:getMovementRotationBitmask(targetRotation, targetX, targetY)
+        if done then
             targetRotation = nil
             targetX = nil
             targetY = nil
+            drawMovementRotationPixel(0)
+        else
+            drawMovementRotationPixel(movementRotationBitmask, headingError, distance)
         end
-        drawMovementRotationPixel(movementRotationBitmask) -- Draw the movement/rotation bitmask
     elseif cmd == "stack" then
         if args == "clear" then
             self.stackedPlayers = {}
@@ -289,7 +408,7 @@ function Multibox:MboxCommandHandler(msg)
         self:Init()
     else
         DEFAULT_CHAT_FRAME:AddMessage("Unknown mbox command: " .. cmd)
-        DEFAULT_CHAT_FRAME:AddMessage("Usage: /mbox [toggle|broadcast|swap <target>|follow <target>|move <rotation> <x> <y>|stack <player1> <player2> ...|stack clear]")
+        DEFAULT_CHAT_FRAME:AddMessage("Usage: /mbox [toggle|broadcast|swap <target>|focus <target> [main]|release|follow <target>|move <rotation> <x> <y>|stack <player1> <player2> ...|stack clear]")
     end
 end
 
@@ -306,10 +425,10 @@ function Multibox:OnCommReceived(prefix, message, distribution, sender)
         if self.keysEnabled ~= newState then
             self.keysEnabled = newState
             if self.keysEnabled then
-                self.controlCommand = 2 -- enable
+                self:SetControlCommand(2) -- enable
                 DEFAULT_CHAT_FRAME:AddMessage("Keys enabled by " .. sender)
             else
-                self.controlCommand = 1 -- disable
+                self:SetControlCommand(1) -- disable
                 DEFAULT_CHAT_FRAME:AddMessage("Keys disabled by " .. sender)
             end
         end
@@ -318,10 +437,10 @@ function Multibox:OnCommReceived(prefix, message, distribution, sender)
         if self.broadcastEnabled ~= newState then
             self.broadcastEnabled = newState
             if self.broadcastEnabled then
-                self.controlCommand = 3 -- enable broadcast
+                self:SetControlCommand(3) -- enable broadcast
                 DEFAULT_CHAT_FRAME:AddMessage("Broadcast enabled by " .. sender)
             else
-                self.controlCommand = 4 -- disable broadcast
+                self:SetControlCommand(4) -- disable broadcast
                 DEFAULT_CHAT_FRAME:AddMessage("Broadcast disabled by " .. sender)
             end
         end
@@ -0,0 +1,98 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicUsize},
+        Arc, Mutex,
+    },
+};
+
+use crate::{
+    crash::CrashTracker,
+    hotkeys::{resolve_hotkeys, Hotkey},
+    keystate::KeyStateTracker,
+    launcher::Launcher,
+    layout::{load_config_file, ConfigFile},
+    recorder::{Recorder, WindowRecording},
+    registry::WindowRegistry,
+    settings::{load_settings, Settings},
+    slots::{load_slot_assignments, SlotAssignments},
+    store::WindowEntry,
+    HwndWrapper,
+};
+
+// Everything the threads of a running instance share. There is one, APP in main.rs,
+// created after command line subcommands are handled. The keyboard hook and the console
+// handler can't be handed any state, so it stays reachable from everywhere.
+pub struct AppState {
+    pub settings: Settings,
+    pub hotkeys: Vec<Hotkey>,
+    // window_config.json, None when it is missing or invalid
+    pub config: Option<Vec<ConfigFile>>,
+    // OMB number of every game window being handled, titles only show them
+    pub registry: Mutex<WindowRegistry<HwndWrapper>>,
+    // Layout slot each OMB window is placed in, changed by swaps and kept across restarts
+    pub slot_assignments: Mutex<SlotAssignments>,
+    pub broadcast_enabled: Mutex<bool>,
+    // Key whose hotkey press was swallowed, so its repeats and key up are swallowed too
+    pub swallowed_key: AtomicU32,
+    // When set, windows keep reading the addon but press no keys and don't move
+    pub automation_paused: AtomicBool,
+    // Index into each slot's "positions" used when placing windows
+    pub active_layout: AtomicUsize,
+    // OMB number of the window leading the group, 0 while there is none
+    pub leader: AtomicUsize,
+    // Set once the tool is stopping; worker threads leave their loops when they see it
+    pub shutdown: AtomicBool,
+    pub shutdown_complete: AtomicBool,
+    // Thread running the message loop, woken with WM_QUIT on shutdown
+    pub message_loop_thread_id: AtomicU32,
+    // Titles windows had before they were renamed, for restoring on shutdown
+    pub original_titles: Mutex<HashMap<HwndWrapper, String>>,
+    // Window styles (GWL_STYLE, GWL_EXSTYLE) windows had before slot styles changed them
+    pub original_styles: Mutex<HashMap<HwndWrapper, (isize, isize)>>,
+    // Key state of every processed window, so held keys can be released from any thread
+    pub key_trackers: Mutex<HashMap<HwndWrapper, Arc<KeyStateTracker>>>,
+    // State of every window seen since startup, for the dashboard and
+    // `MultiboxSoftware status`
+    pub window_status: Mutex<HashMap<HwndWrapper, WindowEntry>>,
+    // Recording of every window's frames and keys, when settings.json asks for one
+    pub recorder: Option<Recorder>,
+    // Where each processed window's events go while recording, for keys sent from outside
+    // its loop without its injector
+    pub recordings: Mutex<HashMap<HwndWrapper, WindowRecording>>,
+    // Clients started from the slots' "launch" entries
+    pub launcher: Mutex<Launcher>,
+    // Crashes of every slot's client since startup
+    pub crashes: Mutex<CrashTracker>,
+}
+
+impl AppState {
+    // Read settings.json, window_config.json and slot_assignments.json
+    pub fn load() -> Self {
+        let settings = load_settings();
+        Self {
+            // Bad hotkey names are reported here, at startup rather than on the first press
+            hotkeys: resolve_hotkeys(&settings.hotkeys),
+            config: load_config_file(),
+            registry: Mutex::new(WindowRegistry::default()),
+            slot_assignments: Mutex::new(load_slot_assignments()),
+            broadcast_enabled: Mutex::new(false),
+            swallowed_key: AtomicU32::new(0),
+            automation_paused: AtomicBool::new(false),
+            active_layout: AtomicUsize::new(0),
+            leader: AtomicUsize::new(0),
+            shutdown: AtomicBool::new(false),
+            shutdown_complete: AtomicBool::new(false),
+            message_loop_thread_id: AtomicU32::new(0),
+            original_titles: Mutex::new(HashMap::new()),
+            original_styles: Mutex::new(HashMap::new()),
+            key_trackers: Mutex::new(HashMap::new()),
+            window_status: Mutex::new(HashMap::new()),
+            recorder: settings.record_file.as_deref().and_then(Recorder::create),
+            recordings: Mutex::new(HashMap::new()),
+            launcher: Mutex::new(Launcher::default()),
+            crashes: Mutex::new(CrashTracker::default()),
+            settings,
+        }
+    }
+}
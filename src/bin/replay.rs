@@ -0,0 +1,30 @@
+// Replays a recording made with "record_file" in settings.json. Builds without Win32, so
+// misbehaving windows can be reproduced on any machine:
+//     cargo run --bin replay -- recording.jsonl
+// The modules are shared with the tool, which uses parts of them this binary doesn't.
+#![allow(dead_code)]
+
+#[path = "../injector.rs"]
+mod injector;
+#[path = "../keystate.rs"]
+mod keystate;
+#[path = "../movement.rs"]
+mod movement;
+#[path = "../pipeline.rs"]
+mod pipeline;
+#[path = "../protocol.rs"]
+mod protocol;
+#[path = "../recorder.rs"]
+mod recorder;
+#[path = "../replay.rs"]
+mod replay;
+
+fn main() {
+    let Some(path) = std::env::args().nth(1) else {
+        println!("Usage: replay <recording_file>");
+        return;
+    };
+    if !replay::replay(&path) {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,264 @@
+use std::time::Duration;
+
+use windows::{
+    core::{w, BOOL, PCWSTR},
+    Win32::{
+        Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, POINT, WPARAM},
+        System::{
+            Console::{SetConsoleCtrlHandler, CTRL_BREAK_EVENT, CTRL_CLOSE_EVENT, CTRL_C_EVENT},
+            LibraryLoader::GetModuleHandleW,
+        },
+        UI::{
+            Shell::{
+                Shell_NotifyIconW, NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD, NIM_DELETE,
+                NOTIFYICONDATAW,
+            },
+            WindowsAndMessaging::{
+                AllowSetForegroundWindow, AppendMenuW, CreatePopupMenu, CreateWindowExW,
+                DefWindowProcW, DestroyMenu, DestroyWindow, FindWindowW, GetCursorPos,
+                GetWindowThreadProcessId, LoadIconW, PostMessageW, RegisterClassW,
+                SetForegroundWindow, TrackPopupMenu, IDI_APPLICATION, MF_STRING, TPM_RETURNCMD,
+                TPM_RIGHTBUTTON, WINDOW_EX_STYLE, WINDOW_STYLE, WM_APP, WM_CLOSE, WM_LBUTTONUP,
+                WM_RBUTTONUP, WNDCLASSW,
+            },
+        },
+    },
+};
+
+use crate::{focus_slot, request_shutdown, wait_for_shutdown};
+
+// Class of the hidden window owned by the running instance.
+// The CLI finds the running instance through it.
+const CONTROL_WINDOW_CLASS: PCWSTR = w!("MultiboxSoftwareControl");
+
+const WM_TRAY_ICON: u32 = WM_APP + 1;
+const TRAY_ICON_ID: u32 = 1;
+const TRAY_MENU_EXIT: usize = 1;
+// Sent by "MultiboxSoftware focus N [main]": wParam = OMB number, lParam = 1 to swap into
+// the main position
+const WM_FOCUS_SLOT: u32 = WM_APP + 2;
+
+// How long a console close may wait for cleanup before Windows kills the process
+const CONSOLE_CLOSE_GRACE: Duration = Duration::from_secs(4);
+
+// Create the hidden control window and its tray icon. Must be called on the thread
+// that runs the message loop.
+pub fn create_control_window() -> Option<HWND> {
+    unsafe {
+        let instance = HINSTANCE(GetModuleHandleW(None).ok()?.0);
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(control_window_proc),
+            hInstance: instance,
+            lpszClassName: CONTROL_WINDOW_CLASS,
+            ..Default::default()
+        };
+        if RegisterClassW(&class) == 0 {
+            println!("Failed to register control window class");
+            return None;
+        }
+
+        let hwnd = match CreateWindowExW(
+            WINDOW_EX_STYLE(0),
+            CONTROL_WINDOW_CLASS,
+            w!("MultiboxSoftware"),
+            WINDOW_STYLE(0),
+            0,
+            0,
+            0,
+            0,
+            None,
+            None,
+            Some(instance),
+            None,
+        ) {
+            Ok(hwnd) => hwnd,
+            Err(e) => {
+                println!("Failed to create control window: {}", e);
+                return None;
+            }
+        };
+
+        let data = tray_icon_data(hwnd);
+        if !Shell_NotifyIconW(NIM_ADD, &data).as_bool() {
+            println!("Failed to add tray icon");
+        }
+        Some(hwnd)
+    }
+}
+
+// Remove the tray icon and destroy the control window
+pub fn destroy_control_window(hwnd: HWND) {
+    unsafe {
+        let data = tray_icon_data(hwnd);
+        let _ = Shell_NotifyIconW(NIM_DELETE, &data);
+        let _ = DestroyWindow(hwnd);
+    }
+}
+
+fn tray_icon_data(hwnd: HWND) -> NOTIFYICONDATAW {
+    let mut data = NOTIFYICONDATAW {
+        cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+        hWnd: hwnd,
+        uID: TRAY_ICON_ID,
+        uFlags: NIF_ICON | NIF_MESSAGE | NIF_TIP,
+        uCallbackMessage: WM_TRAY_ICON,
+        hIcon: unsafe { LoadIconW(None, IDI_APPLICATION) }.unwrap_or_default(),
+        ..Default::default()
+    };
+    for (dst, src) in data.szTip.iter_mut().zip("MultiboxSoftware".encode_utf16()) {
+        *dst = src;
+    }
+    data
+}
+
+fn show_tray_menu(hwnd: HWND) {
+    unsafe {
+        let Ok(menu) = CreatePopupMenu() else {
+            return;
+        };
+        let _ = AppendMenuW(menu, MF_STRING, TRAY_MENU_EXIT, w!("Exit"));
+
+        let mut cursor = POINT::default();
+        let _ = GetCursorPos(&mut cursor);
+        // Without this the menu does not close when clicking elsewhere
+        let _ = SetForegroundWindow(hwnd);
+        let selected = TrackPopupMenu(
+            menu,
+            TPM_RETURNCMD | TPM_RIGHTBUTTON,
+            cursor.x,
+            cursor.y,
+            None,
+            hwnd,
+            None,
+        );
+        let _ = DestroyMenu(menu);
+
+        if selected.0 as usize == TRAY_MENU_EXIT {
+            request_shutdown();
+        }
+    }
+}
+
+unsafe extern "system" fn control_window_proc(
+    hwnd: HWND,
+    msg: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    match msg {
+        WM_TRAY_ICON => {
+            let event = l_param.0 as u32;
+            if event == WM_LBUTTONUP || event == WM_RBUTTONUP {
+                show_tray_menu(hwnd);
+            }
+            LRESULT(0)
+        }
+        // Sent by "MultiboxSoftware stop"; the window is destroyed during shutdown
+        WM_CLOSE => {
+            request_shutdown();
+            LRESULT(0)
+        }
+        WM_FOCUS_SLOT => {
+            focus_slot(w_param.0, l_param.0 != 0);
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, w_param, l_param),
+    }
+}
+
+// Stop cleanly on Ctrl+C, Ctrl+Break or when the console window is closed
+pub fn install_console_handler() {
+    unsafe {
+        if let Err(e) = SetConsoleCtrlHandler(Some(console_ctrl_handler), true) {
+            println!("Failed to install console handler: {}", e);
+        }
+    }
+}
+
+unsafe extern "system" fn console_ctrl_handler(ctrl_type: u32) -> BOOL {
+    match ctrl_type {
+        CTRL_C_EVENT | CTRL_BREAK_EVENT | CTRL_CLOSE_EVENT => {
+            request_shutdown();
+            if ctrl_type == CTRL_CLOSE_EVENT {
+                // The process is terminated as soon as this handler returns
+                wait_for_shutdown(CONSOLE_CLOSE_GRACE);
+            }
+            BOOL::from(true)
+        }
+        _ => BOOL::from(false),
+    }
+}
+
+// Post a message to the control window of an already running instance
+fn send_to_running_instance(msg: u32, w_param: WPARAM, l_param: LPARAM) -> bool {
+    unsafe {
+        match FindWindowW(CONTROL_WINDOW_CLASS, PCWSTR::null()) {
+            Ok(hwnd) => {
+                // The CLI owns the foreground, pass that right on so commands can focus windows
+                let mut process_id = 0;
+                GetWindowThreadProcessId(hwnd, Some(&mut process_id));
+                let _ = AllowSetForegroundWindow(process_id);
+                PostMessageW(Some(hwnd), msg, w_param, l_param).is_ok()
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+// Handle command line subcommands that talk to an already running instance.
+// Returns false when no subcommand was given and the tool should start normally.
+pub fn run_cli_command(args: &[String]) -> bool {
+    let Some(command) = args.first() else {
+        return false;
+    };
+
+    match command.as_str() {
+        "stop" => {
+            if send_to_running_instance(WM_CLOSE, WPARAM(0), LPARAM(0)) {
+                println!("Asked the running instance to stop");
+            } else {
+                println!("No running instance found");
+            }
+        }
+        "focus" => {
+            let number = args.get(1).and_then(|arg| arg.parse::<usize>().ok());
+            let into_main = args.get(2).is_some_and(|arg| arg == "main");
+            match number {
+                Some(number) if number > 0 => {
+                    if send_to_running_instance(
+                        WM_FOCUS_SLOT,
+                        WPARAM(number),
+                        LPARAM(into_main as isize),
+                    ) {
+                        println!("Asked the running instance to focus OMB {}", number);
+                    } else {
+                        println!("No running instance found");
+                    }
+                }
+                _ => println!("Usage: MultiboxSoftware focus <window_number> [main]"),
+            }
+        }
+        "status" => {
+            if unsafe { FindWindowW(CONTROL_WINDOW_CLASS, PCWSTR::null()) }.is_err() {
+                println!("No running instance found, showing the last saved status");
+            }
+            crate::health::print_window_status();
+            crate::crash::print_crashes();
+        }
+        "layout" => {
+            // Edit window_config.json, no running instance needed
+            crate::layout_editor::run();
+        }
+        "generate" => {
+            // Compute a layout for the connected monitors
+            if !crate::layout_generator::run_generate(&args[1..]) {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            println!("Unknown command: {}", command);
+            println!("Usage: MultiboxSoftware [stop|status|focus <window_number> [main]|layout|generate <pattern>]");
+        }
+    }
+    true
+}
@@ -0,0 +1,249 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::health::unix_time;
+
+const CRASHES_FILE: &str = "crashes.json";
+
+// A crash of a client the tool started shows up twice, from its window's thread and from the
+// launcher. Both report the client's process, which counts once. Windows reuses process ids,
+// so a crashed process is only remembered this long.
+const SAME_CRASH_WITHIN: Duration = Duration::from_secs(30);
+
+// How a crash was noticed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrashCause {
+    // The client exited with an error code
+    ExitCode(i32),
+    // The window closed while the addon was drawing its pixels, so the character was in
+    // the world, and the client didn't exit afterwards
+    WindowVanished,
+}
+
+impl std::fmt::Display for CrashCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            // Windows exit codes of crashes are NTSTATUS values, e.g. 0xC0000005
+            CrashCause::ExitCode(code) => write!(f, "exited with code {:#X}", *code as u32),
+            CrashCause::WindowVanished => write!(f, "window vanished in game"),
+        }
+    }
+}
+
+// The crashes of one slot since the tool started, as saved for `MultiboxSoftware status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlotCrashes {
+    pub omb_number: usize,
+    pub count: usize,
+    pub relaunches: usize,
+    pub last_cause: CrashCause,
+    // Seconds since the Unix epoch
+    pub last_at: u64,
+}
+
+// Counts crashes per slot and keeps track of the slots waiting to be relaunched
+#[derive(Debug, Default)]
+pub struct CrashTracker {
+    slots: BTreeMap<usize, SlotCrashes>,
+    // When each crashed process was first reported, by process id
+    reported: BTreeMap<u32, Instant>,
+    pending_relaunches: BTreeSet<usize>,
+}
+
+impl CrashTracker {
+    // Count a crash of a slot's client process. Returns false when it's another sign of a
+    // crash of the same process, which is not counted again.
+    pub fn report(
+        &mut self,
+        omb_number: usize,
+        process_id: u32,
+        cause: CrashCause,
+        now: Instant,
+    ) -> bool {
+        self.reported
+            .retain(|_, &mut at| now.duration_since(at) < SAME_CRASH_WITHIN);
+        if self.reported.contains_key(&process_id) {
+            return false;
+        }
+        self.reported.insert(process_id, now);
+        let slot = self.slots.entry(omb_number).or_insert(SlotCrashes {
+            omb_number,
+            count: 0,
+            relaunches: 0,
+            last_cause: cause,
+            last_at: 0,
+        });
+        slot.count += 1;
+        slot.last_cause = cause;
+        slot.last_at = unix_time();
+        true
+    }
+
+    pub fn count(&self, omb_number: usize) -> usize {
+        self.slots.get(&omb_number).map_or(0, |slot| slot.count)
+    }
+
+    // Queue a crashed slot's client to be started again, unless it was relaunched
+    // max_relaunches times already. Returns whether it was queued.
+    pub fn queue_relaunch(&mut self, omb_number: usize, max_relaunches: usize) -> bool {
+        let relaunches = self
+            .slots
+            .get(&omb_number)
+            .map_or(0, |slot| slot.relaunches);
+        if relaunches >= max_relaunches {
+            return false;
+        }
+        self.pending_relaunches.insert(omb_number);
+        true
+    }
+
+    pub fn pending_relaunches(&self) -> Vec<usize> {
+        self.pending_relaunches.iter().copied().collect()
+    }
+
+    pub fn relaunched(&mut self, omb_number: usize) {
+        self.pending_relaunches.remove(&omb_number);
+        if let Some(slot) = self.slots.get_mut(&omb_number) {
+            slot.relaunches += 1;
+        }
+    }
+
+    pub fn slots(&self) -> Vec<SlotCrashes> {
+        self.slots.values().cloned().collect()
+    }
+}
+
+// Save the crashes of every slot for `MultiboxSoftware status`
+pub fn save_crashes(crashes: &[SlotCrashes]) {
+    match serde_json::to_string_pretty(crashes) {
+        Ok(contents) => {
+            if let Err(e) = std::fs::write(CRASHES_FILE, contents) {
+                println!("Failed to save crashes: {}", e);
+            }
+        }
+        Err(e) => println!("Failed to serialize crashes: {}", e),
+    }
+}
+
+// Print the saved crashes of every slot
+pub fn print_crashes() {
+    let Ok(contents) = std::fs::read_to_string(CRASHES_FILE) else {
+        return;
+    };
+    let crashes: Vec<SlotCrashes> = match serde_json::from_str(&contents) {
+        Ok(crashes) => crashes,
+        Err(e) => {
+            println!("Failed to parse crashes: {}", e);
+            return;
+        }
+    };
+    let now = unix_time();
+    for slot in crashes {
+        println!(
+            "OMB {:<8} {} crashes, {} relaunches, last {} {}s ago",
+            slot.omb_number,
+            slot.count,
+            slot.relaunches,
+            slot.last_cause,
+            now.saturating_sub(slot.last_at)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRASH: CrashCause = CrashCause::ExitCode(0xC0000005_u32 as i32);
+
+    #[test]
+    fn counts_crashes_per_slot() {
+        let now = Instant::now();
+        let mut crashes = CrashTracker::default();
+        assert!(crashes.report(1, 100, CRASH, now));
+        assert!(crashes.report(2, 200, CrashCause::WindowVanished, now));
+        assert!(crashes.report(2, 201, CRASH, now + Duration::from_secs(60)));
+        assert_eq!(crashes.count(1), 1);
+        assert_eq!(crashes.count(2), 2);
+        assert_eq!(crashes.count(3), 0);
+
+        let slots = crashes.slots();
+        assert_eq!(slots.len(), 2);
+        assert_eq!(slots[1].last_cause, CRASH);
+    }
+
+    #[test]
+    fn both_signs_of_one_crash_count_once() {
+        let now = Instant::now();
+        let mut crashes = CrashTracker::default();
+        // The window's thread sees the window vanish, then the launcher sees the exit code
+        assert!(crashes.report(1, 100, CrashCause::WindowVanished, now));
+        assert!(!crashes.report(1, 100, CRASH, now + Duration::from_secs(12)));
+        assert_eq!(crashes.count(1), 1);
+        assert_eq!(crashes.slots()[0].last_cause, CrashCause::WindowVanished);
+    }
+
+    #[test]
+    fn relaunched_client_crashing_again_counts() {
+        let now = Instant::now();
+        let mut crashes = CrashTracker::default();
+        assert!(crashes.report(1, 100, CRASH, now));
+        // The relaunched client is a new process, however soon it crashes
+        assert!(crashes.report(1, 300, CRASH, now + Duration::from_secs(5)));
+        assert_eq!(crashes.count(1), 2);
+    }
+
+    #[test]
+    fn reused_process_id_counts_again_later() {
+        let now = Instant::now();
+        let mut crashes = CrashTracker::default();
+        assert!(crashes.report(1, 100, CRASH, now));
+        assert!(!crashes.report(
+            2,
+            100,
+            CRASH,
+            now + SAME_CRASH_WITHIN - Duration::from_millis(1)
+        ));
+        assert!(crashes.report(2, 100, CRASH, now + SAME_CRASH_WITHIN));
+        assert_eq!(crashes.count(2), 1);
+    }
+
+    #[test]
+    fn queues_relaunches() {
+        let mut crashes = CrashTracker::default();
+        crashes.report(1, 100, CRASH, Instant::now());
+        assert!(crashes.queue_relaunch(1, 3));
+        // Queued once however often it is asked for
+        assert!(crashes.queue_relaunch(1, 3));
+        assert_eq!(crashes.pending_relaunches(), vec![1]);
+
+        crashes.relaunched(1);
+        assert!(crashes.pending_relaunches().is_empty());
+        assert_eq!(crashes.slots()[0].relaunches, 1);
+    }
+
+    #[test]
+    fn stops_relaunching_at_the_limit() {
+        let now = Instant::now();
+        let mut crashes = CrashTracker::default();
+        for process_id in 0..3 {
+            crashes.report(1, process_id, CRASH, now);
+            assert_eq!(crashes.queue_relaunch(1, 2), process_id < 2);
+            if process_id < 2 {
+                crashes.relaunched(1);
+            }
+        }
+        assert!(crashes.pending_relaunches().is_empty());
+        assert_eq!(crashes.slots()[0].relaunches, 2);
+        assert_eq!(crashes.count(1), 3);
+
+        // A limit of 0 never relaunches
+        crashes.report(2, 200, CRASH, now);
+        assert!(!crashes.queue_relaunch(2, 0));
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
+
+use iced::{
+    futures::{channel::mpsc, Stream, StreamExt},
+    widget::{button, column, row, text, Column, Row},
+    Alignment, Element, Length, Subscription, Task,
+};
+
+use crate::{
+    health::WindowState, hotkeys::HotkeyAction, is_shutting_down, layout_count, run_hotkey_action,
+    swap_into_main_slot, APP,
+};
+
+// How often the dashboard reads the shared state
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+// Widths of the table's columns
+const COLUMN_WIDTHS: [f32; 8] = [60.0, 120.0, 110.0, 260.0, 80.0, 80.0, 70.0, 160.0];
+
+#[derive(Debug, Clone)]
+enum Message {
+    Refresh,
+    Action(HotkeyAction),
+    SwapIntoMain(usize),
+}
+
+// One window as shown in the table
+struct WindowRow {
+    omb_number: usize,
+    title: String,
+    character: Option<String>,
+    state: WindowState,
+    last_command: Option<String>,
+    commands_per_minute: usize,
+    capture_latency: Duration,
+    // Crashes of the slot's client since startup
+    crashes: usize,
+}
+
+// Copy of the shared state, taken on every refresh
+struct Dashboard {
+    rows: Vec<WindowRow>,
+    broadcast_enabled: bool,
+    paused: bool,
+    layout: usize,
+    layouts: usize,
+    leader: Option<usize>,
+}
+
+impl Dashboard {
+    fn snapshot() -> Self {
+        let now = Instant::now();
+        let crashes = APP.crashes.lock().unwrap();
+        let mut rows: Vec<WindowRow> = APP
+            .window_status
+            .lock()
+            .unwrap()
+            .values()
+            .map(|entry| WindowRow {
+                omb_number: entry.omb_number,
+                title: entry.status.title.clone(),
+                character: entry.character.clone(),
+                state: entry.status.state,
+                last_command: entry.last_command.clone(),
+                commands_per_minute: entry.commands_per_minute(now),
+                capture_latency: entry.capture_latency,
+                crashes: crashes.count(entry.omb_number),
+            })
+            .collect();
+        drop(crashes);
+        rows.sort_by_key(|row| (row.omb_number, row.title.clone()));
+
+        Self {
+            rows,
+            broadcast_enabled: *APP.broadcast_enabled.lock().unwrap(),
+            paused: APP.automation_paused.load(Ordering::SeqCst),
+            layout: APP.active_layout.load(Ordering::SeqCst),
+            layouts: layout_count(),
+            leader: Some(APP.leader.load(Ordering::SeqCst)).filter(|&leader| leader != 0),
+        }
+    }
+}
+
+// Show the status dashboard until its window is closed or the tool shuts down
+pub fn run() {
+    let result = iced::application("MultiboxSoftware", update, view)
+        .subscription(subscription)
+        .window_size((900.0, 400.0))
+        .run_with(|| (Dashboard::snapshot(), Task::none()));
+    if let Err(e) = result {
+        println!("Failed to run the dashboard: {}", e);
+    }
+}
+
+fn update(dashboard: &mut Dashboard, message: Message) -> Task<Message> {
+    match message {
+        Message::Refresh => {
+            if is_shutting_down() {
+                return iced::exit();
+            }
+        }
+        Message::Action(action) => run_hotkey_action(action, "Dashboard"),
+        Message::SwapIntoMain(number) => swap_into_main_slot(number),
+    }
+    *dashboard = Dashboard::snapshot();
+    Task::none()
+}
+
+fn subscription(_dashboard: &Dashboard) -> Subscription<Message> {
+    Subscription::run(refresh_ticks)
+}
+
+// A Refresh every REFRESH_INTERVAL. iced is built without a timer backend, so one thread
+// does the waiting for as long as the subscription runs.
+fn refresh_ticks() -> impl Stream<Item = Message> {
+    let (mut sender, receiver) = mpsc::channel(1);
+    thread::spawn(move || loop {
+        thread::sleep(REFRESH_INTERVAL);
+        // A full channel only means the last refresh is still being handled
+        if sender.try_send(()).is_err_and(|e| e.is_disconnected()) {
+            break;
+        }
+    });
+    receiver.map(|()| Message::Refresh)
+}
+
+fn view(dashboard: &Dashboard) -> Element<'_, Message> {
+    let controls = row![
+        button(if dashboard.broadcast_enabled {
+            "Stop broadcast"
+        } else {
+            "Broadcast"
+        })
+        .on_press(Message::Action(HotkeyAction::ToggleBroadcast)),
+        button(if dashboard.paused { "Resume" } else { "Pause" })
+            .on_press(Message::Action(HotkeyAction::TogglePause)),
+        button("Next layout").on_press(Message::Action(HotkeyAction::CycleLayout)),
+        button("Launch missing").on_press(Message::Action(HotkeyAction::LaunchMissing)),
+        text(format!(
+            "Broadcast {}, {}, layout {}/{}, leader {}",
+            if dashboard.broadcast_enabled {
+                "on"
+            } else {
+                "off"
+            },
+            if dashboard.paused {
+                "paused"
+            } else {
+                "running"
+            },
+            dashboard.layout + 1,
+            dashboard.layouts,
+            dashboard
+                .leader
+                .map_or("none".to_string(), |leader| format!("OMB {}", leader)),
+        )),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
+
+    let header = table_row(
+        [
+            "Window",
+            "Character",
+            "State",
+            "Last command",
+            "Cmds/min",
+            "Capture",
+            "Crashes",
+            "",
+        ]
+        .map(|title| text(title).into()),
+    );
+    let rows = dashboard.rows.iter().map(|row| {
+        let number = row.omb_number;
+        let actions: Element<Message> = if row.state != WindowState::Closed {
+            row![
+                button("Focus").on_press(Message::Action(HotkeyAction::FocusSlot(number))),
+                button("Make main").on_press(Message::SwapIntoMain(number)),
+            ]
+            .spacing(5)
+            .into()
+        } else {
+            text("").into()
+        };
+        table_row([
+            text(row.title.clone()).into(),
+            text(row.character.clone().unwrap_or_default()).into(),
+            text(format!("{:?}", row.state)).into(),
+            text(row.last_command.clone().unwrap_or_default()).into(),
+            text(row.commands_per_minute).into(),
+            text(format!("{} ms", row.capture_latency.as_millis())).into(),
+            text(row.crashes).into(),
+            actions,
+        ])
+    });
+
+    column![
+        controls,
+        Column::with_children(std::iter::once(header).chain(rows)).spacing(4)
+    ]
+    .spacing(15)
+    .padding(10)
+    .into()
+}
+
+fn table_row(cells: [Element<'_, Message>; 8]) -> Element<'_, Message> {
+    Row::with_children(cells.into_iter().zip(COLUMN_WIDTHS).map(|(cell, width)| {
+        iced::widget::container(cell)
+            .width(Length::Fixed(width))
+            .into()
+    }))
+    .spacing(5)
+    .align_y(Alignment::Center)
+    .into()
+}
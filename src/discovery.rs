@@ -0,0 +1,242 @@
+use std::sync::Mutex;
+
+use crate::{registry::WindowRegistry, titles::parse_slot_tag};
+
+// Class of the game's main window. Older clients add the renderer to it, e.g.
+// "GxWindowClassD3d".
+pub const GAME_WINDOW_CLASS: &str = "GxWindowClass";
+
+// A top level window with its class name and title
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopLevelWindow<W> {
+    pub window: W,
+    pub class: String,
+    pub title: String,
+}
+
+// Where the top level windows come from, so discovery can run against made up ones
+pub trait WindowSource {
+    type Window: Copy + PartialEq;
+    // Every top level window
+    fn windows(&self) -> Vec<TopLevelWindow<Self::Window>>;
+}
+
+// Game windows are told apart by their class. Anything can have "OMB 3" in its title, e.g.
+// a browser showing a page about it.
+pub fn is_game_window(class: &str) -> bool {
+    class.starts_with(GAME_WINDOW_CLASS)
+}
+
+// A game window seen for the first time
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discovered<W> {
+    pub window: W,
+    pub title: String,
+    pub omb_number: usize,
+}
+
+// Register the game windows that aren't registered yet. Each enumeration collects into
+// its own list, so this may run from several threads at once: every window is registered
+// and returned by exactly one of them.
+// `launched` is the OMB number a window's client was started for, if the tool started it.
+// Reserved numbers belong to started clients whose windows haven't shown up yet.
+pub fn discover<S: WindowSource>(
+    source: &S,
+    registry: &Mutex<WindowRegistry<S::Window>>,
+    launched: impl Fn(S::Window) -> Option<usize>,
+    reserved: &[usize],
+) -> Vec<Discovered<S::Window>> {
+    source
+        .windows()
+        .into_iter()
+        .filter(|found| is_game_window(&found.class))
+        .filter_map(|TopLevelWindow { window, title, .. }| {
+            if registry.lock().unwrap().contains(window) {
+                return None;
+            }
+            // A window renamed by an earlier run keeps its number when it's free
+            let requested = launched(window).or_else(|| parse_slot_tag(&title));
+            let mut registry = registry.lock().unwrap();
+            if registry.contains(window) {
+                return None;
+            }
+            let omb_number = registry.register(window, requested, reserved);
+            Some(Discovered {
+                window,
+                title,
+                omb_number,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    // Threads and rounds of the concurrent test
+    const THREADS: usize = 4;
+    const ROUNDS: usize = 2000;
+    // Windows opened and closed while the threads enumerate
+    const CHURN: u32 = 1000;
+
+    // Windows that open and close while they are being enumerated
+    pub struct FakeWindows(Mutex<Vec<TopLevelWindow<u32>>>);
+
+    impl FakeWindows {
+        // Windows numbered from 0, each with its class and title
+        pub fn new(windows: &[(&str, &str)]) -> Self {
+            let source = Self(Mutex::new(Vec::new()));
+            for (window, &(class, title)) in (0..).zip(windows) {
+                source.open(window, class, title);
+            }
+            source
+        }
+
+        pub fn open(&self, window: u32, class: &str, title: &str) {
+            self.0.lock().unwrap().push(TopLevelWindow {
+                window,
+                class: class.to_string(),
+                title: title.to_string(),
+            });
+        }
+
+        pub fn close(&self, window: u32) {
+            self.0
+                .lock()
+                .unwrap()
+                .retain(|found| found.window != window);
+        }
+
+        pub fn is_open(&self, window: u32) -> bool {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|found| found.window == window)
+        }
+    }
+
+    impl WindowSource for FakeWindows {
+        type Window = u32;
+
+        fn windows(&self) -> Vec<TopLevelWindow<u32>> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    #[test]
+    fn windows_get_the_numbers_they_ask_for() {
+        let source = FakeWindows::new(&[
+            (GAME_WINDOW_CLASS, "World of Warcraft"),
+            (GAME_WINDOW_CLASS, "OMB 3"),
+            ("Notepad", "Untitled - Notepad"),
+            (GAME_WINDOW_CLASS, "OMB 3 - Healbot [paused]"),
+            (GAME_WINDOW_CLASS, "World of Warcraft"),
+            (GAME_WINDOW_CLASS, "World of Warcraft"),
+            // Only game windows count, whatever their title says
+            (
+                "MozillaWindowClass",
+                "Re: OMB 6 crash report - Mozilla Firefox",
+            ),
+        ]);
+        let registry = Mutex::new(WindowRegistry::default());
+        // Window 5 belongs to a client started for OMB 2
+        let launched = |window: u32| (window == 5).then_some(2);
+        let numbers: Vec<(u32, usize)> = discover(&source, &registry, launched, &[2])
+            .into_iter()
+            .map(|discovered| (discovered.window, discovered.omb_number))
+            .collect();
+        // The second "OMB 3" can't have its number and gets the lowest free one that isn't
+        // kept for the started client
+        assert_eq!(numbers, vec![(0, 1), (1, 3), (3, 4), (4, 5), (5, 2)]);
+        assert!(
+            discover(&source, &registry, launched, &[2]).is_empty(),
+            "registered windows were discovered again"
+        );
+    }
+
+    #[test]
+    fn concurrent_discovery_registers_each_window_once() {
+        const WINDOWS: [(&str, &str); 4] = [
+            (GAME_WINDOW_CLASS, "World of Warcraft"),
+            (GAME_WINDOW_CLASS, "OMB 2"),
+            ("Notepad", "OMB 4 - Notepad"),
+            (GAME_WINDOW_CLASS, "OMB 5 - Healbot"),
+        ];
+        let source = FakeWindows::new(&[(GAME_WINDOW_CLASS, "World of Warcraft"); 8]);
+        let registry = Mutex::new(WindowRegistry::default());
+        let found = Mutex::new(Vec::new());
+
+        std::thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    for _ in 0..ROUNDS {
+                        for discovered in discover(&source, &registry, |_| None, &[]) {
+                            // A window that closed while it was being registered is let go,
+                            // the way a window's thread unregisters it when the window is
+                            // gone
+                            if source.is_open(discovered.window) {
+                                found.lock().unwrap().push(discovered.window);
+                            } else {
+                                registry.lock().unwrap().unregister(discovered.window);
+                            }
+                        }
+                        let windows = registry.lock().unwrap().windows();
+                        let unique: HashSet<u32> =
+                            windows.iter().map(|&(_, window)| window).collect();
+                        assert_eq!(
+                            unique.len(),
+                            windows.len(),
+                            "a window has two numbers: {:?}",
+                            windows
+                        );
+                    }
+                });
+            }
+            scope.spawn(|| {
+                for step in 0..CHURN {
+                    let window = 100 + step;
+                    let (class, title) = WINDOWS[step as usize % WINDOWS.len()];
+                    source.open(window, class, title);
+                    // Close the first windows and then the oldest, keeping a handful open
+                    let closed = if step < 8 { step } else { window - 6 };
+                    source.close(closed);
+                    registry.lock().unwrap().unregister(closed);
+                    std::thread::yield_now();
+                }
+            });
+        });
+
+        let mut found = found.into_inner().unwrap();
+        found.extend(
+            discover(&source, &registry, |_| None, &[])
+                .into_iter()
+                .map(|discovered| discovered.window),
+        );
+        let unique: HashSet<u32> = found.iter().copied().collect();
+        assert_eq!(
+            unique.len(),
+            found.len(),
+            "open windows were discovered more than once"
+        );
+        let mut registered: Vec<u32> = registry
+            .lock()
+            .unwrap()
+            .windows()
+            .into_iter()
+            .map(|(_, window)| window)
+            .collect();
+        registered.sort();
+        let mut open: Vec<u32> = source
+            .windows()
+            .into_iter()
+            .filter(|found| is_game_window(&found.class))
+            .map(|found| found.window)
+            .collect();
+        open.sort();
+        assert_eq!(registered, open);
+    }
+}
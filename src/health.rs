@@ -0,0 +1,361 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::movement::{Clock, SystemClock};
+
+const WINDOW_STATUS_FILE: &str = "window_status.json";
+
+// The addon flips its heartbeat bit several times a second. When it hasn't flipped for this
+// long the addon's OnUpdate stopped running, e.g. the game froze or a Lua error stopped it.
+pub const STALE_AFTER: Duration = Duration::from_secs(2);
+
+// Transitions kept per window in the status
+const MAX_TRANSITIONS: usize = 10;
+
+// What the tool knows about a game window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowState {
+    // Found, but not captured yet
+    Discovered,
+    // The window's client area could not be captured
+    CaptureFailed,
+    // Captured, but the sentinel pixel isn't there: the addon is not installed, the UI is
+    // reloading or a loading screen is up
+    AddonMissing,
+    // The addon draws its pixels and its heartbeat changes
+    Active,
+    // The sentinel pixel is there, but the heartbeat stopped changing
+    Stale,
+    // The window is gone
+    Closed,
+}
+
+// What one capture of a window showed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Observation {
+    CaptureFailed,
+    AddonMissing,
+    Addon { heartbeat: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub from: WindowState,
+    pub to: WindowState,
+    // How long the window was in the previous state
+    pub after: Duration,
+}
+
+// Follows the state of one window from its captures
+pub struct WindowHealth<C: Clock = SystemClock> {
+    clock: C,
+    state: WindowState,
+    since: Instant,
+    // Last heartbeat value seen and when it last changed
+    heartbeat: Option<(bool, Instant)>,
+}
+
+impl WindowHealth {
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<C: Clock> WindowHealth<C> {
+    pub fn with_clock(clock: C) -> Self {
+        Self {
+            since: clock.now(),
+            clock,
+            state: WindowState::Discovered,
+            heartbeat: None,
+        }
+    }
+
+    // Returns the transition when the observation changed the state
+    pub fn observe(&mut self, observation: Observation) -> Option<Transition> {
+        let now = self.clock.now();
+        let state = match observation {
+            Observation::CaptureFailed => WindowState::CaptureFailed,
+            Observation::AddonMissing => WindowState::AddonMissing,
+            Observation::Addon { heartbeat } => {
+                let changed_at = match self.heartbeat {
+                    Some((last, changed_at)) if last == heartbeat => changed_at,
+                    _ => now,
+                };
+                self.heartbeat = Some((heartbeat, changed_at));
+                if now.duration_since(changed_at) >= STALE_AFTER {
+                    WindowState::Stale
+                } else {
+                    WindowState::Active
+                }
+            }
+        };
+        if !matches!(observation, Observation::Addon { .. }) {
+            // Staleness is only judged while the addon is seen without a break
+            self.heartbeat = None;
+        }
+        self.enter(state, now)
+    }
+
+    pub fn close(&mut self) -> Option<Transition> {
+        self.enter(WindowState::Closed, self.clock.now())
+    }
+
+    fn enter(&mut self, state: WindowState, now: Instant) -> Option<Transition> {
+        if state == self.state {
+            return None;
+        }
+        let transition = Transition {
+            from: self.state,
+            to: state,
+            after: now.duration_since(self.since),
+        };
+        self.state = state;
+        self.since = now;
+        Some(transition)
+    }
+}
+
+// A state change as saved in the status file
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct StatusChange {
+    pub from: WindowState,
+    pub to: WindowState,
+    // Seconds since the Unix epoch
+    pub at: u64,
+}
+
+// The state of one window, as shown to the UI and the CLI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowStatus {
+    pub title: String,
+    pub state: WindowState,
+    // Seconds since the Unix epoch
+    pub since: u64,
+    // The latest transitions, oldest first
+    pub transitions: Vec<StatusChange>,
+}
+
+impl WindowStatus {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            state: WindowState::Discovered,
+            since: unix_time(),
+            transitions: Vec::new(),
+        }
+    }
+
+    pub fn apply(&mut self, transition: &Transition) {
+        let at = unix_time();
+        self.state = transition.to;
+        self.since = at;
+        self.transitions.push(StatusChange {
+            from: transition.from,
+            to: transition.to,
+            at,
+        });
+        if self.transitions.len() > MAX_TRANSITIONS {
+            self.transitions.remove(0);
+        }
+    }
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+// Save the status of every window for `MultiboxSoftware status`
+pub fn save_window_status(statuses: &[WindowStatus]) {
+    match serde_json::to_string_pretty(statuses) {
+        Ok(contents) => {
+            if let Err(e) = std::fs::write(WINDOW_STATUS_FILE, contents) {
+                println!("Failed to save window status: {}", e);
+            }
+        }
+        Err(e) => println!("Failed to serialize window status: {}", e),
+    }
+}
+
+pub fn load_window_status() -> Option<Vec<WindowStatus>> {
+    let contents = std::fs::read_to_string(WINDOW_STATUS_FILE).ok()?;
+    match serde_json::from_str(&contents) {
+        Ok(statuses) => Some(statuses),
+        Err(e) => {
+            println!("Failed to parse window status: {}", e);
+            None
+        }
+    }
+}
+
+// Print the saved status of every window
+pub fn print_window_status() {
+    let Some(statuses) = load_window_status() else {
+        println!("No window status saved yet");
+        return;
+    };
+    let now = unix_time();
+    for status in statuses {
+        println!(
+            "{:<12} {:<14} for {}s",
+            status.title,
+            format!("{:?}", status.state),
+            now.saturating_sub(status.since)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movement::SimulatedClock;
+
+    const FRAME: Duration = Duration::from_millis(100);
+
+    fn addon(heartbeat: bool) -> Observation {
+        Observation::Addon { heartbeat }
+    }
+
+    fn transition(from: WindowState, to: WindowState, after: Duration) -> Option<Transition> {
+        Some(Transition { from, to, after })
+    }
+
+    #[test]
+    fn follows_a_window_from_discovered_to_closed() {
+        let clock = SimulatedClock::new();
+        let mut health = WindowHealth::with_clock(clock.clone());
+
+        clock.advance(FRAME);
+        assert_eq!(
+            health.observe(addon(false)),
+            transition(WindowState::Discovered, WindowState::Active, FRAME)
+        );
+
+        // The heartbeat stops flipping
+        let mut stale = None;
+        let mut elapsed = Duration::ZERO;
+        while stale.is_none() {
+            clock.advance(FRAME);
+            elapsed += FRAME;
+            stale = health.observe(addon(false));
+        }
+        assert_eq!(
+            stale,
+            transition(WindowState::Active, WindowState::Stale, STALE_AFTER)
+        );
+        assert_eq!(elapsed, STALE_AFTER);
+
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(
+            health.close(),
+            transition(
+                WindowState::Stale,
+                WindowState::Closed,
+                Duration::from_secs(3)
+            )
+        );
+        assert_eq!(health.close(), None, "a closed window closes once");
+    }
+
+    #[test]
+    fn stale_threshold() {
+        let clock = SimulatedClock::new();
+        let mut health = WindowHealth::with_clock(clock.clone());
+        health.observe(addon(true));
+
+        clock.advance(STALE_AFTER - Duration::from_millis(1));
+        assert_eq!(
+            health.observe(addon(true)),
+            None,
+            "still active just before"
+        );
+
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(
+            health.observe(addon(true)).map(|transition| transition.to),
+            Some(WindowState::Stale)
+        );
+    }
+
+    #[test]
+    fn heartbeat_flip_makes_a_stale_window_active_again() {
+        let clock = SimulatedClock::new();
+        let mut health = WindowHealth::with_clock(clock.clone());
+        health.observe(addon(false));
+        clock.advance(STALE_AFTER);
+        health.observe(addon(false));
+
+        clock.advance(FRAME);
+        assert_eq!(
+            health.observe(addon(true)),
+            transition(WindowState::Stale, WindowState::Active, FRAME)
+        );
+        // The flip restarts the wait
+        clock.advance(STALE_AFTER - FRAME);
+        assert_eq!(health.observe(addon(true)), None);
+    }
+
+    #[test]
+    fn losing_the_addon_restarts_the_wait() {
+        let clock = SimulatedClock::new();
+        let mut health = WindowHealth::with_clock(clock.clone());
+        health.observe(addon(false));
+        clock.advance(STALE_AFTER - FRAME);
+
+        assert_eq!(
+            health
+                .observe(Observation::AddonMissing)
+                .map(|transition| transition.to),
+            Some(WindowState::AddonMissing)
+        );
+        clock.advance(FRAME);
+        assert_eq!(
+            health.observe(addon(false)).map(|transition| transition.to),
+            Some(WindowState::Active),
+            "the heartbeat seen before the break doesn't count"
+        );
+        clock.advance(STALE_AFTER - FRAME);
+        assert_eq!(health.observe(addon(false)), None);
+    }
+
+    #[test]
+    fn capture_failures_are_their_own_state() {
+        let clock = SimulatedClock::new();
+        let mut health = WindowHealth::with_clock(clock.clone());
+        assert_eq!(
+            health.observe(Observation::CaptureFailed),
+            transition(
+                WindowState::Discovered,
+                WindowState::CaptureFailed,
+                Duration::ZERO
+            )
+        );
+        clock.advance(FRAME);
+        assert_eq!(health.observe(Observation::CaptureFailed), None);
+        assert_eq!(
+            health.close(),
+            transition(WindowState::CaptureFailed, WindowState::Closed, FRAME)
+        );
+    }
+
+    #[test]
+    fn status_keeps_the_latest_transitions() {
+        let mut status = WindowStatus::new("OMB 1");
+        let states = [WindowState::Active, WindowState::Stale];
+        for i in 0..MAX_TRANSITIONS + 3 {
+            status.apply(&Transition {
+                from: states[(i + 1) % 2],
+                to: states[i % 2],
+                after: FRAME,
+            });
+        }
+        assert_eq!(status.transitions.len(), MAX_TRANSITIONS);
+        assert_eq!(status.state, WindowState::Active);
+        assert_eq!(status.transitions.last().unwrap().to, WindowState::Active);
+    }
+}
@@ -0,0 +1,252 @@
+use serde::{Deserialize, Serialize};
+
+// What a global hotkey does when pressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HotkeyAction {
+    ToggleBroadcast,
+    // Stop pressing keys and moving in every window, without touching broadcast
+    TogglePause,
+    // Move every window to its rectangle in the next entry of its slot's "positions"
+    CycleLayout,
+    // Swap the focused game window with the one in slot 1
+    SwapWithMain,
+    // Bring the window in the given slot to the foreground
+    FocusSlot(usize),
+    // Swap the window in the given slot into the main position and focus it
+    FocusSlotAsMain(usize),
+    // Start the clients of slots with a "launch" entry that have no window
+    LaunchMissing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+}
+
+// A hotkey as written in settings.json, e.g.
+// { "key": "F12", "modifiers": ["ctrl", "shift"], "action": "toggle_broadcast" }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HotkeyConfig {
+    pub key: String,
+    #[serde(default)]
+    pub modifiers: Vec<Modifier>,
+    pub action: HotkeyAction,
+}
+
+// Which modifiers are held while a key is pressed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ModifierState {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl ModifierState {
+    fn from_modifiers(modifiers: &[Modifier]) -> Self {
+        let mut state = Self::default();
+        for modifier in modifiers {
+            match modifier {
+                Modifier::Ctrl => state.ctrl = true,
+                Modifier::Shift => state.shift = true,
+                Modifier::Alt => state.alt = true,
+            }
+        }
+        state
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hotkey {
+    pub vk: u16,
+    pub modifiers: ModifierState,
+    pub action: HotkeyAction,
+}
+
+// Resolve configured hotkeys to virtual-key codes, skipping entries with unknown key names
+pub fn resolve_hotkeys(configs: &[HotkeyConfig]) -> Vec<Hotkey> {
+    let mut hotkeys = Vec::new();
+    for config in configs {
+        match parse_key_name(&config.key) {
+            Some(vk) => hotkeys.push(Hotkey {
+                vk,
+                modifiers: ModifierState::from_modifiers(&config.modifiers),
+                action: config.action,
+            }),
+            None => println!("Unknown hotkey key name: {}", config.key),
+        }
+    }
+    hotkeys
+}
+
+// The action bound to a key press. Modifiers must match exactly, so CTRL-SHIFT-F12
+// does not trigger a hotkey bound to CTRL-F12.
+pub fn find_hotkey(hotkeys: &[Hotkey], vk: u16, modifiers: ModifierState) -> Option<HotkeyAction> {
+    hotkeys
+        .iter()
+        .find(|hotkey| hotkey.vk == vk && hotkey.modifiers == modifiers)
+        .map(|hotkey| hotkey.action)
+}
+
+// Map a key name (case-insensitive) to its virtual-key code:
+// letters, digits, F1-F24, NUMPAD0-NUMPAD9 and a few named keys
+pub fn parse_key_name(name: &str) -> Option<u16> {
+    let name = name.trim().to_ascii_uppercase();
+
+    if let [c] = name.as_bytes() {
+        if c.is_ascii_uppercase() || c.is_ascii_digit() {
+            // Virtual-key codes for letters and digits are their ASCII values
+            return Some(*c as u16);
+        }
+    }
+
+    if let Some(number) = name.strip_prefix("NUMPAD") {
+        return match number.parse::<u16>() {
+            Ok(n) if n <= 9 => Some(0x60 + n),
+            _ => None,
+        };
+    }
+
+    if let Some(number) = name.strip_prefix('F') {
+        if let Ok(n) = number.parse::<u16>() {
+            return (1..=24).contains(&n).then_some(0x70 + n - 1);
+        }
+    }
+
+    match name.as_str() {
+        "BACKSPACE" => Some(0x08),
+        "TAB" => Some(0x09),
+        "ENTER" => Some(0x0D),
+        "PAUSE" => Some(0x13),
+        "ESCAPE" | "ESC" => Some(0x1B),
+        "SPACE" => Some(0x20),
+        "PAGEUP" => Some(0x21),
+        "PAGEDOWN" => Some(0x22),
+        "END" => Some(0x23),
+        "HOME" => Some(0x24),
+        "INSERT" => Some(0x2D),
+        "DELETE" => Some(0x2E),
+        "SCROLLLOCK" => Some(0x91),
+        "BACKQUOTE" | "`" => Some(0xC0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hotkeys(json: &str) -> Vec<Hotkey> {
+        resolve_hotkeys(&serde_json::from_str::<Vec<HotkeyConfig>>(json).unwrap())
+    }
+
+    #[test]
+    fn parses_key_names() {
+        let cases = [
+            ("a", Some(0x41)),
+            ("Z", Some(0x5A)),
+            ("7", Some(0x37)),
+            ("f1", Some(0x70)),
+            ("F24", Some(0x87)),
+            ("numpad0", Some(0x60)),
+            ("NUMPAD9", Some(0x69)),
+            (" Escape ", Some(0x1B)),
+            ("esc", Some(0x1B)),
+            ("PageUp", Some(0x21)),
+            ("`", Some(0xC0)),
+            ("F0", None),
+            ("F25", None),
+            ("NUMPAD10", None),
+            ("NUMPAD", None),
+            ("AB", None),
+            ("CTRL", None),
+            ("", None),
+        ];
+        for (name, vk) in cases {
+            assert_eq!(parse_key_name(name), vk, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn parses_modifiers() {
+        let hotkeys = hotkeys(
+            r#"[
+                { "key": "F12", "modifiers": ["ctrl", "shift"], "action": "toggle_broadcast" },
+                { "key": "F11", "action": "toggle_pause" },
+                { "key": "1", "modifiers": ["alt"], "action": { "focus_slot": 1 } }
+            ]"#,
+        );
+        let modifiers = |ctrl, shift, alt| ModifierState { ctrl, shift, alt };
+        assert_eq!(
+            hotkeys,
+            vec![
+                Hotkey {
+                    vk: 0x7B,
+                    modifiers: modifiers(true, true, false),
+                    action: HotkeyAction::ToggleBroadcast,
+                },
+                Hotkey {
+                    vk: 0x7A,
+                    modifiers: ModifierState::default(),
+                    action: HotkeyAction::TogglePause,
+                },
+                Hotkey {
+                    vk: 0x31,
+                    modifiers: modifiers(false, false, true),
+                    action: HotkeyAction::FocusSlot(1),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_modifier_is_an_error() {
+        assert!(serde_json::from_str::<HotkeyConfig>(
+            r#"{ "key": "F12", "modifiers": ["win"], "action": "toggle_broadcast" }"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn unknown_key_names_are_skipped() {
+        let hotkeys = hotkeys(
+            r#"[
+                { "key": "F99", "action": "toggle_broadcast" },
+                { "key": "F10", "action": "cycle_layout" }
+            ]"#,
+        );
+        assert_eq!(hotkeys.len(), 1);
+        assert_eq!(hotkeys[0].action, HotkeyAction::CycleLayout);
+    }
+
+    #[test]
+    fn modifiers_must_match_exactly() {
+        let hotkeys = hotkeys(
+            r#"[
+                { "key": "F12", "modifiers": ["ctrl"], "action": "toggle_broadcast" },
+                { "key": "F12", "modifiers": ["ctrl", "shift"], "action": "swap_with_main" }
+            ]"#,
+        );
+        let ctrl = ModifierState {
+            ctrl: true,
+            ..Default::default()
+        };
+        let ctrl_shift = ModifierState {
+            shift: true,
+            ..ctrl
+        };
+        assert_eq!(
+            find_hotkey(&hotkeys, 0x7B, ctrl),
+            Some(HotkeyAction::ToggleBroadcast)
+        );
+        assert_eq!(
+            find_hotkey(&hotkeys, 0x7B, ctrl_shift),
+            Some(HotkeyAction::SwapWithMain)
+        );
+        assert_eq!(find_hotkey(&hotkeys, 0x7B, ModifierState::default()), None);
+        assert_eq!(find_hotkey(&hotkeys, 0x7A, ctrl), None);
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+// How key events are delivered to a window, selectable per slot in window_config.json
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InjectionMethod {
+    // Post WM_KEYDOWN/WM_KEYUP to the window's queue. Works in the background, but posted
+    // modifiers do not change the keyboard state the client sees.
+    #[default]
+    PostMessage,
+    // Bring the window to the foreground and synthesize real input with SendInput.
+    SendInput,
+    // Post messages, but attach to the window's input thread and mirror modifier state
+    // with SetKeyboardState so CTRL/SHIFT/ALT bindings resolve correctly.
+    KeyboardState,
+    // Send nothing, only log and record the events (dry run).
+    Record,
+}
+
+// Delivers key events to a single game window.
+// Key codes are raw virtual-key values so callers and fakes don't depend on Win32 types.
+pub trait KeyInjector: Send + Sync {
+    fn key_down(&self, vk: u16);
+    fn key_up(&self, vk: u16);
+
+    // Press and immediately release a key
+    fn tap(&self, vk: u16) {
+        self.key_down(vk);
+        self.key_up(vk);
+    }
+
+    // Press a key while an optional modifier is held down
+    fn chord(&self, modifier: Option<u16>, vk: u16) {
+        if let Some(modifier) = modifier {
+            self.key_down(modifier);
+        }
+        self.tap(vk);
+        if let Some(modifier) = modifier {
+            self.key_up(modifier);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InjectedKey {
+    pub vk: u16,
+    pub down: bool,
+}
+
+// Records key events instead of sending them. Used as a dry-run strategy and as a fake.
+pub struct RecordingInjector {
+    // Prefix of the line printed for every event, None to record silently
+    label: Option<String>,
+    events: Mutex<Vec<InjectedKey>>,
+}
+
+impl RecordingInjector {
+    // A dry run that prints every event under the window's label
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: Some(label.to_string()),
+            events: Mutex::new(Vec::new()),
+        }
+    }
+
+    // A fake that only records
+    #[cfg(test)]
+    pub fn silent() -> Self {
+        Self {
+            label: None,
+            events: Mutex::new(Vec::new()),
+        }
+    }
+
+    // All events recorded so far, in order
+    #[cfg(test)]
+    pub fn events(&self) -> Vec<InjectedKey> {
+        self.events.lock().unwrap().clone()
+    }
+
+    fn record(&self, vk: u16, down: bool) {
+        if let Some(label) = &self.label {
+            println!(
+                "[{}] Recorded key {} {}",
+                label,
+                vk,
+                if down { "down" } else { "up" }
+            );
+        }
+        self.events.lock().unwrap().push(InjectedKey { vk, down });
+    }
+}
+
+impl KeyInjector for RecordingInjector {
+    fn key_down(&self, vk: u16) {
+        self.record(vk, true);
+    }
+
+    fn key_up(&self, vk: u16) {
+        self.record(vk, false);
+    }
+}
+
+// Lets a test keep hold of a fake it handed over as a Box<dyn KeyInjector>
+#[cfg(test)]
+impl KeyInjector for std::sync::Arc<RecordingInjector> {
+    fn key_down(&self, vk: u16) {
+        self.as_ref().key_down(vk);
+    }
+
+    fn key_up(&self, vk: u16) {
+        self.as_ref().key_up(vk);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VK_SHIFT: u16 = 0x10;
+    const VK_1: u16 = 0x31;
+
+    fn down(vk: u16) -> InjectedKey {
+        InjectedKey { vk, down: true }
+    }
+
+    fn up(vk: u16) -> InjectedKey {
+        InjectedKey { vk, down: false }
+    }
+
+    #[test]
+    fn tap_presses_and_releases() {
+        let injector = RecordingInjector::silent();
+        injector.tap(VK_1);
+        assert_eq!(injector.events(), vec![down(VK_1), up(VK_1)]);
+    }
+
+    #[test]
+    fn chord_wraps_the_tap_in_the_modifier() {
+        let injector = RecordingInjector::silent();
+        injector.chord(Some(VK_SHIFT), VK_1);
+        assert_eq!(
+            injector.events(),
+            vec![down(VK_SHIFT), down(VK_1), up(VK_1), up(VK_SHIFT)]
+        );
+    }
+
+    #[test]
+    fn chord_without_modifier_is_a_tap() {
+        let injector = RecordingInjector::silent();
+        injector.chord(None, VK_1);
+        assert_eq!(injector.events(), vec![down(VK_1), up(VK_1)]);
+    }
+}
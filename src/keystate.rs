@@ -0,0 +1,238 @@
+use std::{collections::BTreeSet, sync::Mutex};
+
+use crate::injector::KeyInjector;
+
+// Tracks which keys are held down in one window.
+// Wraps the window's injector so every key sent through it is accounted for. The lock is held
+// while events are sent, so a tap or chord from one thread never interleaves with keys from
+// another and the held set always matches what the window was sent.
+pub struct KeyStateTracker {
+    injector: Box<dyn KeyInjector>,
+    held: Mutex<BTreeSet<u16>>,
+}
+
+impl KeyStateTracker {
+    pub fn new(injector: Box<dyn KeyInjector>) -> Self {
+        Self {
+            injector,
+            held: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    // Keys currently held down, in virtual-key order
+    pub fn held_keys(&self) -> Vec<u16> {
+        self.held.lock().unwrap().iter().copied().collect()
+    }
+
+    // Release every held key
+    pub fn release_all(&self) {
+        let mut held = self.held.lock().unwrap();
+        for vk in std::mem::take(&mut *held) {
+            self.injector.key_up(vk);
+        }
+    }
+
+    // Tap a key while the lock on the held keys is taken
+    fn tap_with(&self, held: &BTreeSet<u16>, vk: u16) {
+        if held.contains(&vk) {
+            // Tapping a held key would release it behind the tracker's back
+            self.injector.key_up(vk);
+            self.injector.key_down(vk);
+        } else {
+            self.injector.tap(vk);
+        }
+    }
+}
+
+impl KeyInjector for KeyStateTracker {
+    // Pressing a key that is already down does nothing
+    fn key_down(&self, vk: u16) {
+        let mut held = self.held.lock().unwrap();
+        if held.insert(vk) {
+            self.injector.key_down(vk);
+        }
+    }
+
+    // Releasing a key that is not down does nothing
+    fn key_up(&self, vk: u16) {
+        let mut held = self.held.lock().unwrap();
+        if held.remove(&vk) {
+            self.injector.key_up(vk);
+        }
+    }
+
+    fn tap(&self, vk: u16) {
+        let held = self.held.lock().unwrap();
+        self.tap_with(&held, vk);
+    }
+
+    fn chord(&self, modifier: Option<u16>, vk: u16) {
+        let held = self.held.lock().unwrap();
+        // A modifier that is already down must stay down afterwards
+        let modifier = modifier.filter(|modifier| !held.contains(modifier));
+        if let Some(modifier) = modifier {
+            self.injector.key_down(modifier);
+        }
+        self.tap_with(&held, vk);
+        if let Some(modifier) = modifier {
+            self.injector.key_up(modifier);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::injector::{InjectedKey, RecordingInjector};
+
+    const VK_SHIFT: u16 = 0x10;
+    const VK_W: u16 = 0x57;
+    const VK_1: u16 = 0x31;
+
+    fn down(vk: u16) -> InjectedKey {
+        InjectedKey { vk, down: true }
+    }
+
+    fn up(vk: u16) -> InjectedKey {
+        InjectedKey { vk, down: false }
+    }
+
+    // Gives other threads a chance to run before each event is recorded, like a real
+    // injector that takes a while to post a message
+    struct SlowInjector(Arc<RecordingInjector>);
+
+    impl KeyInjector for SlowInjector {
+        fn key_down(&self, vk: u16) {
+            std::thread::yield_now();
+            self.0.key_down(vk);
+        }
+
+        fn key_up(&self, vk: u16) {
+            std::thread::yield_now();
+            self.0.key_up(vk);
+        }
+    }
+
+    fn tracker() -> (KeyStateTracker, Arc<RecordingInjector>) {
+        let injector = Arc::new(RecordingInjector::silent());
+        (KeyStateTracker::new(Box::new(injector.clone())), injector)
+    }
+
+    #[test]
+    fn presses_and_releases_each_key_once() {
+        let (tracker, injector) = tracker();
+        tracker.key_down(VK_W);
+        tracker.key_down(VK_W);
+        assert_eq!(tracker.held_keys(), vec![VK_W]);
+        tracker.key_up(VK_W);
+        tracker.key_up(VK_W);
+        tracker.key_up(VK_1);
+        assert!(tracker.held_keys().is_empty());
+        assert_eq!(injector.events(), vec![down(VK_W), up(VK_W)]);
+    }
+
+    #[test]
+    fn tapping_a_held_key_keeps_it_held() {
+        let (tracker, injector) = tracker();
+        tracker.key_down(VK_W);
+        tracker.tap(VK_W);
+        assert_eq!(tracker.held_keys(), vec![VK_W]);
+        assert_eq!(injector.events(), vec![down(VK_W), up(VK_W), down(VK_W)]);
+    }
+
+    #[test]
+    fn chord_leaves_a_held_modifier_down() {
+        let (tracker, injector) = tracker();
+        tracker.key_down(VK_SHIFT);
+        tracker.chord(Some(VK_SHIFT), VK_1);
+        assert_eq!(tracker.held_keys(), vec![VK_SHIFT]);
+        assert_eq!(
+            injector.events(),
+            vec![down(VK_SHIFT), down(VK_1), up(VK_1)]
+        );
+    }
+
+    #[test]
+    fn chord_keeps_a_held_key_held() {
+        let (tracker, injector) = tracker();
+        tracker.key_down(VK_1);
+        tracker.chord(Some(VK_SHIFT), VK_1);
+        assert_eq!(tracker.held_keys(), vec![VK_1]);
+        assert_eq!(
+            injector.events(),
+            vec![
+                down(VK_1),
+                down(VK_SHIFT),
+                up(VK_1),
+                down(VK_1),
+                up(VK_SHIFT)
+            ]
+        );
+
+        tracker.key_down(VK_SHIFT);
+        tracker.chord(Some(VK_SHIFT), VK_1);
+        assert_eq!(tracker.held_keys(), vec![VK_SHIFT, VK_1]);
+        assert_eq!(injector.events()[6..], [up(VK_1), down(VK_1)]);
+    }
+
+    #[test]
+    fn chord_presses_a_free_modifier() {
+        let (tracker, injector) = tracker();
+        tracker.chord(Some(VK_SHIFT), VK_1);
+        assert!(tracker.held_keys().is_empty());
+        assert_eq!(
+            injector.events(),
+            vec![down(VK_SHIFT), down(VK_1), up(VK_1), up(VK_SHIFT)]
+        );
+    }
+
+    #[test]
+    fn keys_from_different_threads_never_interleave() {
+        let injector = Arc::new(RecordingInjector::silent());
+        let tracker = Arc::new(KeyStateTracker::new(Box::new(SlowInjector(
+            injector.clone(),
+        ))));
+        let threads: Vec<_> = (0..4)
+            .map(|thread| {
+                let tracker = tracker.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..2000 {
+                        if thread % 2 == 0 {
+                            tracker.tap(VK_W);
+                        } else {
+                            tracker.key_down(VK_W);
+                            tracker.key_up(VK_W);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // Replaying the events must never press a held key or release a free one
+        let mut is_down = false;
+        for event in injector.events() {
+            assert_ne!(event.down, is_down, "{:?} sent out of order", event);
+            is_down = event.down;
+        }
+        assert!(!is_down);
+        assert!(tracker.held_keys().is_empty());
+    }
+
+    #[test]
+    fn release_all_releases_every_held_key() {
+        let (tracker, injector) = tracker();
+        tracker.key_down(VK_W);
+        tracker.key_down(VK_SHIFT);
+        tracker.release_all();
+        assert!(tracker.held_keys().is_empty());
+        assert_eq!(
+            injector.events(),
+            vec![down(VK_W), down(VK_SHIFT), up(VK_SHIFT), up(VK_W)]
+        );
+    }
+}
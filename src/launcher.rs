@@ -0,0 +1,143 @@
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    process::{Child, Command, ExitStatus},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+// How a slot's client is started, from the slot's "launch" entry in window_config.json, e.g.
+// { "exe": "C:\\WoW\\_classic_\\WowClassic.exe", "args": ["-config", "Config2.wtf"] }
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaunchConfig {
+    pub exe: String,
+    // Directory the client starts in, the executable's own when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    // Added to the tool's own environment, e.g. WINEPREFIX
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+impl LaunchConfig {
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.exe);
+        command.args(&self.args).envs(&self.env);
+        match &self.working_dir {
+            Some(dir) => {
+                command.current_dir(dir);
+            }
+            None => {
+                if let Some(dir) = Path::new(&self.exe)
+                    .parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                {
+                    command.current_dir(dir);
+                }
+            }
+        }
+        command
+    }
+}
+
+// Launcher settings from settings.json, e.g.
+// { "at_startup": true, "delay_ms": 3000, "relaunch_crashed": true, "max_relaunches": 3 }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaunchSettings {
+    // Start the client of every slot with a "launch" entry and no window when the tool
+    // starts
+    pub at_startup: bool,
+    // Time between starting two clients, so they don't all load at once
+    pub delay_ms: u64,
+    // Start the client of a slot again when it crashed
+    pub relaunch_crashed: bool,
+    // Relaunches per slot, so a client that crashes while starting isn't started forever
+    pub max_relaunches: usize,
+}
+
+impl Default for LaunchSettings {
+    fn default() -> Self {
+        Self {
+            at_startup: true,
+            delay_ms: 3000,
+            relaunch_crashed: false,
+            max_relaunches: 3,
+        }
+    }
+}
+
+impl LaunchSettings {
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms)
+    }
+}
+
+// The clients the tool started, by the OMB number they were started for
+#[derive(Debug, Default)]
+pub struct Launcher {
+    children: BTreeMap<usize, Child>,
+}
+
+impl Launcher {
+    // Start a slot's client. Returns the id of its process.
+    pub fn launch(&mut self, omb_number: usize, config: &LaunchConfig) -> Result<u32, String> {
+        if self.is_running(omb_number) {
+            return Err(format!(
+                "The client of OMB {} is already running",
+                omb_number
+            ));
+        }
+        let child = config
+            .command()
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", config.exe, e))?;
+        let process_id = child.id();
+        self.children.insert(omb_number, child);
+        Ok(process_id)
+    }
+
+    pub fn is_running(&mut self, omb_number: usize) -> bool {
+        self.children
+            .get_mut(&omb_number)
+            .is_some_and(|child| matches!(child.try_wait(), Ok(None)))
+    }
+
+    // The OMB number a process was started for
+    pub fn slot_of_process(&self, process_id: u32) -> Option<usize> {
+        self.children
+            .iter()
+            .find(|(_, child)| child.id() == process_id)
+            .map(|(&omb_number, _)| omb_number)
+    }
+
+    // OMB numbers of clients that are running, kept free for their windows
+    pub fn running(&mut self) -> Vec<usize> {
+        self.children
+            .iter_mut()
+            .filter_map(|(&omb_number, child)| {
+                matches!(child.try_wait(), Ok(None)).then_some(omb_number)
+            })
+            .collect()
+    }
+
+    // Forget the clients that exited since the last call. Returns the OMB number, process
+    // id and how each one exited.
+    pub fn reap(&mut self) -> Vec<(usize, u32, ExitStatus)> {
+        let exited: Vec<(usize, u32, ExitStatus)> = self
+            .children
+            .iter_mut()
+            .filter_map(|(&omb_number, child)| {
+                let status = child.try_wait().ok().flatten()?;
+                Some((omb_number, child.id(), status))
+            })
+            .collect();
+        for (omb_number, _, _) in &exited {
+            self.children.remove(omb_number);
+        }
+        exited
+    }
+}
@@ -0,0 +1,418 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    injector::InjectionMethod,
+    launcher::LaunchConfig,
+    monitors::{find_monitor, Monitor},
+};
+
+pub const CONFIG_FILE: &str = "window_config.json";
+
+// Windows smaller than this can't show the game, let alone its pixels
+pub const MIN_WINDOW_SIZE: i32 = 64;
+
+// A rectangle on the desktop, in physical pixels
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn right(&self) -> i32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.right()
+            && other.x < self.right()
+            && self.y < other.bottom()
+            && other.y < self.bottom()
+    }
+
+    #[cfg(test)]
+    pub fn contains(&self, other: &Rect) -> bool {
+        self.x <= other.x
+            && self.y <= other.y
+            && other.right() <= self.right()
+            && other.bottom() <= self.bottom()
+    }
+
+    // The smallest rectangle holding both
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect {
+            x,
+            y,
+            width: self.right().max(other.right()) - x,
+            height: self.bottom().max(other.bottom()) - y,
+        }
+    }
+}
+
+// A monitor, by its number (1 is the primary monitor, the others follow from left to
+// right) or by its device name, e.g. "DISPLAY2"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MonitorRef {
+    Number(usize),
+    Name(String),
+}
+
+impl std::fmt::Display for MonitorRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MonitorRef::Number(number) => write!(f, "{}", number),
+            MonitorRef::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Units {
+    // Pixels as the monitor shows them
+    #[default]
+    Physical,
+    // Pixels at 100% scaling, multiplied by the monitor's scale factor when placing
+    Logical,
+}
+
+impl Units {
+    fn is_physical(&self) -> bool {
+        *self == Units::Physical
+    }
+}
+
+// Configuration structs for window positions and sizes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowConfig {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    // Monitor x and y are relative to, desktop coordinates when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<MonitorRef>,
+    // Logical units are scaled by the given monitor, or by the primary one when none is given
+    #[serde(default, skip_serializing_if = "Units::is_physical")]
+    pub units: Units,
+}
+
+impl WindowConfig {
+    // A position in physical desktop coordinates
+    pub fn absolute(rect: Rect) -> Self {
+        Self {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+            monitor: None,
+            units: Units::Physical,
+        }
+    }
+
+    // A position in physical pixels relative to a monitor's top left corner
+    pub fn on_monitor(rect: Rect, monitor: MonitorRef) -> Self {
+        Self {
+            monitor: Some(monitor),
+            ..Self::absolute(rect)
+        }
+    }
+
+    // Origin and scale factor the position is given in
+    fn frame(&self, monitors: &[Monitor]) -> Result<(i32, i32, f32), String> {
+        let monitor = match &self.monitor {
+            Some(reference) => Some(
+                find_monitor(monitors, reference)
+                    .ok_or_else(|| format!("There is no monitor {}", reference))?,
+            ),
+            None => None,
+        };
+        let scale = match self.units {
+            Units::Physical => 1.0,
+            Units::Logical => monitor
+                .or(monitors.iter().find(|monitor| monitor.primary))
+                .map_or(1.0, |monitor| monitor.scale),
+        };
+        Ok(monitor.map_or((0, 0, scale), |monitor| {
+            (monitor.bounds.x, monitor.bounds.y, scale)
+        }))
+    }
+
+    // Where the window goes, in physical desktop coordinates
+    pub fn resolve(&self, monitors: &[Monitor]) -> Result<Rect, String> {
+        let (x, y, scale) = self.frame(monitors)?;
+        let scaled = |value: i32| (value as f32 * scale).round() as i32;
+        Ok(Rect {
+            x: x + scaled(self.x),
+            y: y + scaled(self.y),
+            width: scaled(self.width),
+            height: scaled(self.height),
+        })
+    }
+
+    // Move the position to a rectangle in physical desktop coordinates, keeping the monitor
+    // and units it is given in. Falls back to desktop coordinates when its monitor is gone.
+    pub fn set_rect(&mut self, rect: Rect, monitors: &[Monitor]) {
+        let Ok((x, y, scale)) = self.frame(monitors) else {
+            *self = Self::absolute(rect);
+            return;
+        };
+        let unscaled = |value: i32| (value as f32 / scale).round() as i32;
+        self.x = unscaled(rect.x - x);
+        self.y = unscaled(rect.y - y);
+        self.width = unscaled(rect.width);
+        self.height = unscaled(rect.height);
+    }
+}
+
+// How a slot's window looks, applied whenever a window is placed in the slot and put back
+// on shutdown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowStyle {
+    // No title bar or border, so the client area is the whole window
+    pub borderless: bool,
+    pub hide_from_taskbar: bool,
+    pub always_on_top: bool,
+    // Clicks go to whatever is behind the window
+    pub click_through: bool,
+}
+
+impl WindowStyle {
+    fn is_default(&self) -> bool {
+        *self == WindowStyle::default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigFile {
+    pub positions: Vec<WindowConfig>,
+    #[serde(default)]
+    pub injection: InjectionMethod,
+    // Name of the character played in this slot's window, shown in the dashboard
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub character: Option<String>,
+    #[serde(default, skip_serializing_if = "WindowStyle::is_default")]
+    pub style: WindowStyle,
+    // How to start this slot's client, the tool never starts it when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub launch: Option<LaunchConfig>,
+}
+
+// Load the slot configuration, None when the file is missing or invalid
+pub fn load_config_file() -> Option<Vec<ConfigFile>> {
+    match std::fs::read_to_string(CONFIG_FILE) {
+        Ok(contents) => match serde_json::from_str::<Vec<ConfigFile>>(&contents) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                println!("Failed to parse JSON: {}", e);
+                None
+            }
+        },
+        Err(e) => {
+            println!("Failed to read config file: {}", e);
+            None
+        }
+    }
+}
+
+pub fn save_config_file(config: &[ConfigFile]) -> Result<(), String> {
+    let contents = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize window config: {}", e))?;
+    std::fs::write(CONFIG_FILE, contents)
+        .map_err(|e| format!("Failed to save {}: {}", CONFIG_FILE, e))
+}
+
+// Problems that would make a slot configuration unusable, empty when there are none.
+// Windows are only checked against the monitors when some are known.
+pub fn validate_config(config: &[ConfigFile], monitors: &[Monitor]) -> Vec<String> {
+    let mut problems = Vec::new();
+    if config.is_empty() {
+        problems.push("There are no slots".to_string());
+    }
+    for (index, slot) in config.iter().enumerate() {
+        let omb_number = index + 1;
+        if slot.positions.is_empty() {
+            problems.push(format!("OMB {} has no positions", omb_number));
+        }
+        for (layout, position) in slot.positions.iter().enumerate() {
+            let rect = match position.resolve(monitors) {
+                Ok(rect) => rect,
+                Err(e) => {
+                    problems.push(format!(
+                        "OMB {} in layout {}: {}",
+                        omb_number,
+                        layout + 1,
+                        e
+                    ));
+                    continue;
+                }
+            };
+            if rect.width < MIN_WINDOW_SIZE || rect.height < MIN_WINDOW_SIZE {
+                problems.push(format!(
+                    "OMB {} in layout {} is {}x{}, smaller than {}x{}",
+                    omb_number,
+                    layout + 1,
+                    rect.width,
+                    rect.height,
+                    MIN_WINDOW_SIZE,
+                    MIN_WINDOW_SIZE
+                ));
+            }
+            if !monitors.is_empty()
+                && !monitors
+                    .iter()
+                    .any(|monitor| monitor.bounds.intersects(&rect))
+            {
+                problems.push(format!(
+                    "OMB {} in layout {} is not on any monitor",
+                    omb_number,
+                    layout + 1
+                ));
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitors::{FixedMonitors, MonitorSource};
+
+    // A 150% primary monitor with a 125% one to its right, a little higher up
+    fn monitors() -> Vec<Monitor> {
+        FixedMonitors(vec![
+            Monitor {
+                name: "\\\\.\\DISPLAY1".to_string(),
+                bounds: Rect {
+                    x: 0,
+                    y: 0,
+                    width: 3840,
+                    height: 2160,
+                },
+                primary: true,
+                scale: 1.5,
+            },
+            Monitor {
+                name: "\\\\.\\DISPLAY2".to_string(),
+                bounds: Rect {
+                    x: 3840,
+                    y: -200,
+                    width: 2560,
+                    height: 1440,
+                },
+                primary: false,
+                scale: 1.25,
+            },
+        ])
+        .monitors()
+    }
+
+    fn rect(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn position(json: &str) -> WindowConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn physical_desktop_position_is_used_as_is() {
+        let position = position(r#"{ "x": 100, "y": 50, "width": 1280, "height": 720 }"#);
+        assert_eq!(position.units, Units::Physical);
+        assert_eq!(position.resolve(&monitors()), Ok(rect(100, 50, 1280, 720)));
+    }
+
+    #[test]
+    fn logical_desktop_position_is_scaled_by_the_primary_monitor() {
+        let position =
+            position(r#"{ "x": 100, "y": 50, "width": 1280, "height": 720, "units": "logical" }"#);
+        assert_eq!(position.resolve(&monitors()), Ok(rect(150, 75, 1920, 1080)));
+    }
+
+    #[test]
+    fn position_is_relative_to_its_monitor() {
+        let by_number =
+            position(r#"{ "x": 10, "y": 20, "width": 1280, "height": 720, "monitor": 2 }"#);
+        assert_eq!(
+            by_number.resolve(&monitors()),
+            Ok(rect(3850, -180, 1280, 720))
+        );
+        let by_name = position(
+            r#"{ "x": 10, "y": 20, "width": 1280, "height": 720, "monitor": "display2" }"#,
+        );
+        assert_eq!(
+            by_name.monitor,
+            Some(MonitorRef::Name("display2".to_string()))
+        );
+        assert_eq!(by_name.resolve(&monitors()), by_number.resolve(&monitors()));
+    }
+
+    #[test]
+    fn logical_position_is_scaled_by_its_own_monitor() {
+        let position = position(
+            r#"{ "x": 80, "y": 40, "width": 1024, "height": 576, "monitor": 2, "units": "logical" }"#,
+        );
+        assert_eq!(
+            position.resolve(&monitors()),
+            Ok(rect(3940, -150, 1280, 720))
+        );
+    }
+
+    #[test]
+    fn missing_monitor_is_an_error() {
+        let position = WindowConfig::on_monitor(rect(0, 0, 640, 360), MonitorRef::Number(3));
+        assert_eq!(
+            position.resolve(&monitors()),
+            Err("There is no monitor 3".to_string())
+        );
+    }
+
+    #[test]
+    fn set_rect_keeps_monitor_and_units() {
+        let mut position = position(
+            r#"{ "x": 80, "y": 40, "width": 1024, "height": 576, "monitor": 2, "units": "logical" }"#,
+        );
+        position.set_rect(rect(3840, -200, 2560, 1440), &monitors());
+        assert_eq!(
+            position,
+            WindowConfig {
+                x: 0,
+                y: 0,
+                width: 2048,
+                height: 1152,
+                monitor: Some(MonitorRef::Number(2)),
+                units: Units::Logical,
+            }
+        );
+        assert_eq!(
+            position.resolve(&monitors()),
+            Ok(rect(3840, -200, 2560, 1440))
+        );
+    }
+
+    #[test]
+    fn set_rect_falls_back_to_the_desktop_when_the_monitor_is_gone() {
+        let mut position = WindowConfig {
+            units: Units::Logical,
+            ..WindowConfig::on_monitor(rect(0, 0, 640, 360), MonitorRef::Number(3))
+        };
+        position.set_rect(rect(100, 100, 800, 450), &monitors());
+        assert_eq!(position, WindowConfig::absolute(rect(100, 100, 800, 450)));
+    }
+}
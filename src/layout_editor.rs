@@ -0,0 +1,541 @@
+use iced::{
+    border, event, mouse,
+    widget::{
+        button, checkbox, column, container, mouse_area, row, text, text_input, Space, Stack,
+    },
+    Alignment, Color, Element, Event, Length, Padding, Point, Subscription, Task,
+};
+
+use crate::{
+    layout::{
+        load_config_file, save_config_file, validate_config, ConfigFile, Rect, Units, WindowConfig,
+        CONFIG_FILE, MIN_WINDOW_SIZE,
+    },
+    monitors::{Monitor, MonitorSource},
+    win32_monitors::Win32Monitors,
+};
+
+// Size of the area the desktop is drawn in, in logical pixels
+const CANVAS_WIDTH: f32 = 960.0;
+const CANVAS_HEIGHT: f32 = 540.0;
+const CANVAS_MARGIN: f32 = 10.0;
+// Size of the handle in a slot's bottom right corner that resizes it
+const RESIZE_HANDLE: f32 = 10.0;
+// Edges closer than this on screen snap together
+const SNAP_DISTANCE: f32 = 8.0;
+
+const MONITOR_COLOR: Color = Color::from_rgb(0.85, 0.85, 0.85);
+const SLOT_COLOR: Color = Color::from_rgba(0.2, 0.45, 0.8, 0.5);
+const SELECTED_COLOR: Color = Color::from_rgba(0.9, 0.5, 0.1, 0.6);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DragMode {
+    Move,
+    Resize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    X,
+    Y,
+    Width,
+    Height,
+}
+
+#[derive(Debug, Clone)]
+enum Message {
+    CursorMoved(Point),
+    Grab(usize, DragMode),
+    Release,
+    Select(usize),
+    Edit(Field, String),
+    PreviousLayout,
+    NextLayout,
+    AddSlot,
+    RemoveLastSlot,
+    ToggleSnap(bool),
+    Save,
+    Revert,
+}
+
+struct Drag {
+    slot: usize,
+    mode: DragMode,
+    // Desktop position the drag started at and the slot's rectangle at that time
+    start: (i32, i32),
+    rect: Rect,
+}
+
+// Maps between desktop coordinates and the canvas
+#[derive(Debug, Clone, Copy)]
+struct View {
+    desktop: Rect,
+    scale: f32,
+}
+
+impl View {
+    // Fit everything that is drawn into the canvas
+    fn fit(monitors: &[Monitor], config: &[ConfigFile]) -> Self {
+        let desktop = monitors
+            .iter()
+            .map(|monitor| monitor.bounds)
+            .chain(config.iter().flat_map(|slot| {
+                slot.positions
+                    .iter()
+                    .filter_map(|position| position.resolve(monitors).ok())
+            }))
+            .reduce(|a, b| a.union(&b))
+            .unwrap_or(Rect {
+                x: 0,
+                y: 0,
+                width: 1920,
+                height: 1080,
+            });
+        let scale = ((CANVAS_WIDTH - 2.0 * CANVAS_MARGIN) / desktop.width.max(1) as f32)
+            .min((CANVAS_HEIGHT - 2.0 * CANVAS_MARGIN) / desktop.height.max(1) as f32);
+        Self { desktop, scale }
+    }
+
+    fn to_canvas(self, rect: &Rect) -> (f32, f32, f32, f32) {
+        (
+            CANVAS_MARGIN + (rect.x - self.desktop.x) as f32 * self.scale,
+            CANVAS_MARGIN + (rect.y - self.desktop.y) as f32 * self.scale,
+            rect.width as f32 * self.scale,
+            rect.height as f32 * self.scale,
+        )
+    }
+
+    fn to_desktop(self, point: Point) -> (i32, i32) {
+        (
+            self.desktop.x + ((point.x - CANVAS_MARGIN) / self.scale).round() as i32,
+            self.desktop.y + ((point.y - CANVAS_MARGIN) / self.scale).round() as i32,
+        )
+    }
+}
+
+struct LayoutEditor {
+    config: Vec<ConfigFile>,
+    monitors: Vec<Monitor>,
+    view: View,
+    // Index into each slot's positions being edited
+    layout: usize,
+    selected: Option<usize>,
+    drag: Option<Drag>,
+    cursor: Point,
+    snap: bool,
+    status: String,
+}
+
+impl LayoutEditor {
+    fn load() -> Self {
+        let monitors = Win32Monitors.monitors();
+        let (config, status) = match load_config_file() {
+            Some(config) => (config, format!("Loaded {}", CONFIG_FILE)),
+            None => (
+                Vec::new(),
+                format!("{} not loaded, starting empty", CONFIG_FILE),
+            ),
+        };
+        Self {
+            view: View::fit(&monitors, &config),
+            config,
+            monitors,
+            layout: 0,
+            selected: None,
+            drag: None,
+            cursor: Point::ORIGIN,
+            snap: true,
+            status,
+        }
+    }
+
+    fn layout_count(&self) -> usize {
+        self.config
+            .iter()
+            .map(|slot| slot.positions.len())
+            .max()
+            .unwrap_or(1)
+            .max(self.layout + 1)
+    }
+
+    // The slot's position in the edited layout. Like the tool, slots with fewer positions
+    // fall back to their first one.
+    fn position(&self, slot: usize) -> Option<&WindowConfig> {
+        let positions = &self.config.get(slot)?.positions;
+        positions.get(self.layout).or(positions.first())
+    }
+
+    // Where the slot's window goes in the edited layout, in desktop coordinates.
+    // Positions on a monitor that isn't connected can't be shown.
+    fn rect(&self, slot: usize) -> Option<Rect> {
+        self.position(slot)?.resolve(&self.monitors).ok()
+    }
+
+    fn set_rect(&mut self, slot: usize, rect: Rect) {
+        let Some(positions) = self.config.get_mut(slot).map(|slot| &mut slot.positions) else {
+            return;
+        };
+        // Give the slot positions up to the edited layout, copying what it falls back to
+        let fallback = positions
+            .first()
+            .cloned()
+            .unwrap_or_else(|| WindowConfig::absolute(rect));
+        while positions.len() <= self.layout {
+            positions.push(fallback.clone());
+        }
+        // Keep the monitor and units the position is given in
+        positions[self.layout].set_rect(rect, &self.monitors);
+    }
+
+    // Edges a moved or resized slot snaps to: the monitors' and the other slots'
+    fn snap_edges(&self, slot: usize) -> (Vec<i32>, Vec<i32>) {
+        let rects = self.monitors.iter().map(|monitor| monitor.bounds).chain(
+            (0..self.config.len())
+                .filter(|&other| other != slot)
+                .filter_map(|other| self.rect(other)),
+        );
+        let mut vertical = Vec::new();
+        let mut horizontal = Vec::new();
+        for rect in rects {
+            vertical.extend([rect.x, rect.right()]);
+            horizontal.extend([rect.y, rect.bottom()]);
+        }
+        (vertical, horizontal)
+    }
+
+    fn drag_to(&mut self, point: Point) {
+        let Some(drag) = &self.drag else {
+            return;
+        };
+        let (x, y) = self.view.to_desktop(point);
+        let (dx, dy) = (x - drag.start.0, y - drag.start.1);
+        let (slot, mode, mut rect) = (drag.slot, drag.mode, drag.rect);
+        let (vertical, horizontal) = self.snap_edges(slot);
+        let distance = (SNAP_DISTANCE / self.view.scale).round() as i32;
+        let snap = |edges: &[i32], candidates: &[i32]| -> i32 {
+            if !self.snap {
+                return 0;
+            }
+            // The smallest shift that puts one of the candidates on an edge
+            candidates
+                .iter()
+                .flat_map(|&candidate| edges.iter().map(move |&edge| edge - candidate))
+                .filter(|shift| shift.abs() <= distance)
+                .min_by_key(|shift| shift.abs())
+                .unwrap_or(0)
+        };
+        match mode {
+            DragMode::Move => {
+                rect.x += dx;
+                rect.y += dy;
+                rect.x += snap(&vertical, &[rect.x, rect.right()]);
+                rect.y += snap(&horizontal, &[rect.y, rect.bottom()]);
+            }
+            DragMode::Resize => {
+                rect.width = (rect.width + dx).max(MIN_WINDOW_SIZE);
+                rect.height = (rect.height + dy).max(MIN_WINDOW_SIZE);
+                rect.width += snap(&vertical, &[rect.right()]);
+                rect.height += snap(&horizontal, &[rect.bottom()]);
+                rect.width = rect.width.max(MIN_WINDOW_SIZE);
+                rect.height = rect.height.max(MIN_WINDOW_SIZE);
+            }
+        }
+        self.set_rect(slot, rect);
+    }
+
+    fn edit(&mut self, field: Field, value: &str) {
+        let Some(slot) = self.selected else {
+            return;
+        };
+        let (Some(mut rect), Ok(value)) = (self.rect(slot), value.parse::<i32>()) else {
+            return;
+        };
+        match field {
+            Field::X => rect.x = value,
+            Field::Y => rect.y = value,
+            Field::Width => rect.width = value,
+            Field::Height => rect.height = value,
+        }
+        self.set_rect(slot, rect);
+    }
+
+    fn save(&mut self) {
+        let problems = validate_config(&self.config, &self.monitors);
+        self.status = if problems.is_empty() {
+            match save_config_file(&self.config) {
+                Ok(()) => format!("Saved {}, restart the tool to use it", CONFIG_FILE),
+                Err(e) => e,
+            }
+        } else {
+            format!("Not saved:\n{}", problems.join("\n"))
+        };
+    }
+}
+
+// Edit window_config.json by dragging the slots around on a picture of the monitors
+pub fn run() {
+    let result = iced::application("MultiboxSoftware layout editor", update, view)
+        .subscription(subscription)
+        .window_size((CANVAS_WIDTH + 280.0, CANVAS_HEIGHT + 120.0))
+        .run_with(|| (LayoutEditor::load(), Task::none()));
+    if let Err(e) = result {
+        println!("Failed to run the layout editor: {}", e);
+    }
+}
+
+fn update(editor: &mut LayoutEditor, message: Message) -> Task<Message> {
+    match message {
+        Message::CursorMoved(point) => {
+            editor.cursor = point;
+            editor.drag_to(point);
+        }
+        Message::Grab(slot, mode) => {
+            if let Some(rect) = editor.rect(slot) {
+                editor.selected = Some(slot);
+                editor.drag = Some(Drag {
+                    slot,
+                    mode,
+                    start: editor.view.to_desktop(editor.cursor),
+                    rect,
+                });
+            }
+        }
+        Message::Release => editor.drag = None,
+        Message::Select(slot) => editor.selected = Some(slot),
+        Message::Edit(field, value) => editor.edit(field, &value),
+        Message::PreviousLayout => editor.layout = editor.layout.saturating_sub(1),
+        Message::NextLayout => editor.layout += 1,
+        Message::AddSlot => {
+            // Start the new slot next to the last one
+            let rect = editor
+                .config
+                .len()
+                .checked_sub(1)
+                .and_then(|last| editor.rect(last))
+                .map(|rect| Rect {
+                    x: rect.right(),
+                    ..rect
+                })
+                .unwrap_or(Rect {
+                    x: editor.view.desktop.x,
+                    y: editor.view.desktop.y,
+                    width: 320,
+                    height: 180,
+                });
+            editor.config.push(ConfigFile {
+                positions: vec![WindowConfig::absolute(rect)],
+                injection: Default::default(),
+                character: None,
+                style: Default::default(),
+                launch: None,
+            });
+            editor.selected = Some(editor.config.len() - 1);
+        }
+        Message::RemoveLastSlot => {
+            editor.config.pop();
+            editor.selected = editor.selected.filter(|&slot| slot < editor.config.len());
+        }
+        Message::ToggleSnap(snap) => editor.snap = snap,
+        Message::Save => editor.save(),
+        Message::Revert => *editor = LayoutEditor::load(),
+    }
+    Task::none()
+}
+
+// Drags end wherever the button is released, also outside the canvas
+fn subscription(_editor: &LayoutEditor) -> Subscription<Message> {
+    event::listen_with(|event, _, _| match event {
+        Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => Some(Message::Release),
+        _ => None,
+    })
+}
+
+fn view(editor: &LayoutEditor) -> Element<'_, Message> {
+    let mut canvas = Stack::new()
+        .width(Length::Fixed(CANVAS_WIDTH))
+        .height(Length::Fixed(CANVAS_HEIGHT))
+        .push(Space::new(Length::Fill, Length::Fill));
+
+    for (index, monitor) in editor.monitors.iter().enumerate() {
+        let label = format!(
+            "{}: {} {}x{}{}",
+            index + 1,
+            monitor.name,
+            monitor.bounds.width,
+            monitor.bounds.height,
+            if monitor.primary { " (primary)" } else { "" }
+        );
+        canvas = canvas.push(place(
+            &editor.view,
+            &monitor.bounds,
+            container(text(label).size(12))
+                .padding(4)
+                .style(|_| boxed(MONITOR_COLOR, Color::from_rgb(0.5, 0.5, 0.5)))
+                .into(),
+        ));
+    }
+
+    // The selected slot is drawn last, so it is on top
+    let order = (0..editor.config.len())
+        .filter(|&slot| Some(slot) != editor.selected)
+        .chain(editor.selected);
+    for slot in order {
+        let Some(rect) = editor.rect(slot) else {
+            continue;
+        };
+        let color = if Some(slot) == editor.selected {
+            SELECTED_COLOR
+        } else {
+            SLOT_COLOR
+        };
+        let handle = mouse_area(
+            container(Space::new(
+                Length::Fixed(RESIZE_HANDLE),
+                Length::Fixed(RESIZE_HANDLE),
+            ))
+            .style(|_| boxed(Color::WHITE, Color::BLACK)),
+        )
+        .on_press(Message::Grab(slot, DragMode::Resize))
+        .interaction(mouse::Interaction::ResizingDiagonallyDown);
+        let body = column![
+            text(format!("OMB {}", slot + 1)).size(12),
+            Space::with_height(Length::Fill),
+            row![Space::with_width(Length::Fill), handle],
+        ]
+        .width(Length::Fill)
+        .height(Length::Fill);
+        canvas = canvas.push(place(
+            &editor.view,
+            &rect,
+            mouse_area(
+                container(body)
+                    .padding(2)
+                    .style(move |_| boxed(color, Color::BLACK)),
+            )
+            .on_press(Message::Grab(slot, DragMode::Move))
+            .interaction(mouse::Interaction::Grab)
+            .into(),
+        ));
+    }
+
+    let canvas = mouse_area(
+        container(canvas).style(|_| boxed(Color::from_rgb(0.97, 0.97, 0.97), Color::BLACK)),
+    )
+    .on_move(Message::CursorMoved);
+
+    column![
+        row![canvas, side_panel(editor)].spacing(10),
+        text(&editor.status).size(14),
+    ]
+    .spacing(10)
+    .padding(10)
+    .into()
+}
+
+fn side_panel(editor: &LayoutEditor) -> Element<'_, Message> {
+    let layouts = row![
+        button("<").on_press(Message::PreviousLayout),
+        text(format!(
+            "Layout {}/{}",
+            editor.layout + 1,
+            editor.layout_count()
+        )),
+        button(">").on_press(Message::NextLayout),
+    ]
+    .spacing(5)
+    .align_y(Alignment::Center);
+
+    let slots = row((0..editor.config.len()).map(|slot| {
+        button(text(slot + 1))
+            .on_press(Message::Select(slot))
+            .into()
+    }))
+    .spacing(2)
+    .wrap();
+
+    let selected: Element<Message> = match editor.selected.and_then(|slot| editor.rect(slot)) {
+        Some(rect) => {
+            let field = |label: &'static str, field: Field, value: i32| {
+                row![
+                    text(label).width(Length::Fixed(60.0)),
+                    text_input("", &value.to_string())
+                        .on_input(move |value| Message::Edit(field, value))
+                        .width(Length::Fixed(100.0)),
+                ]
+                .align_y(Alignment::Center)
+            };
+            column![
+                text(format!("OMB {}", editor.selected.unwrap_or_default() + 1)),
+                text(saved_as(
+                    editor.selected.and_then(|slot| editor.position(slot))
+                ))
+                .size(12),
+                field("x", Field::X, rect.x),
+                field("y", Field::Y, rect.y),
+                field("width", Field::Width, rect.width),
+                field("height", Field::Height, rect.height),
+            ]
+            .spacing(5)
+            .into()
+        }
+        None => text("Click a slot to select it").into(),
+    };
+
+    column![
+        layouts,
+        slots,
+        selected,
+        row![
+            button("Add slot").on_press(Message::AddSlot),
+            button("Remove last").on_press(Message::RemoveLastSlot),
+        ]
+        .spacing(5),
+        checkbox("Snap to edges", editor.snap).on_toggle(Message::ToggleSnap),
+        row![
+            button("Save").on_press(Message::Save),
+            button("Revert").on_press(Message::Revert),
+        ]
+        .spacing(5),
+    ]
+    .spacing(15)
+    .width(Length::Fixed(260.0))
+    .into()
+}
+
+// How a position is written to the file. The editor always shows physical desktop
+// coordinates.
+fn saved_as(position: Option<&WindowConfig>) -> String {
+    let Some(position) = position else {
+        return String::new();
+    };
+    let units = match position.units {
+        Units::Physical => "physical",
+        Units::Logical => "logical",
+    };
+    match &position.monitor {
+        Some(monitor) => format!("Saved relative to monitor {}, in {} pixels", monitor, units),
+        None => format!("Saved in desktop coordinates, in {} pixels", units),
+    }
+}
+
+// Put an element over a desktop rectangle on the canvas
+fn place<'a>(view: &View, rect: &Rect, content: Element<'a, Message>) -> Element<'a, Message> {
+    let (x, y, width, height) = view.to_canvas(rect);
+    container(
+        container(content)
+            .width(Length::Fixed(width.max(1.0)))
+            .height(Length::Fixed(height.max(1.0))),
+    )
+    .padding(Padding::ZERO.left(x.max(0.0)).top(y.max(0.0)))
+    .width(Length::Fill)
+    .height(Length::Fill)
+    .into()
+}
+
+fn boxed(background: Color, border_color: Color) -> container::Style {
+    container::Style {
+        background: Some(background.into()),
+        border: border::rounded(0).color(border_color).width(1),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,396 @@
+use crate::{
+    layout::{
+        load_config_file, save_config_file, ConfigFile, MonitorRef, Rect, WindowConfig,
+        CONFIG_FILE, MIN_WINDOW_SIZE,
+    },
+    monitors::{Monitor, MonitorSource},
+    win32_monitors::Win32Monitors,
+};
+
+// Followers in a strip or column keep the game's 16:9 shape, and take at most this share
+// of the monitor from the main window
+const FOLLOWER_SHARE: i32 = 3;
+
+// How windows are spread over the monitors. Slot 1 is always the first window generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    // Equal cells, filled row by row
+    Grid { columns: usize, rows: usize },
+    // The main window on top, followers in a row along the bottom
+    MainStrip { followers: usize },
+    // The main window on the left, followers in a column on the right
+    MainColumn { followers: usize },
+    // One window filling each monitor
+    PerMonitor,
+}
+
+impl Pattern {
+    // Parse "grid 3x2", "strip 4", "column 4" or "fill"
+    pub fn parse(args: &[String]) -> Option<Self> {
+        let count = || args.get(1)?.parse::<usize>().ok().filter(|&n| n > 0);
+        match args.first()?.as_str() {
+            "grid" => {
+                let (columns, rows) = args.get(1)?.split_once('x')?;
+                let (columns, rows) = (columns.parse().ok()?, rows.parse().ok()?);
+                (columns > 0 && rows > 0).then_some(Pattern::Grid { columns, rows })
+            }
+            "strip" => Some(Pattern::MainStrip {
+                followers: count()?,
+            }),
+            "column" => Some(Pattern::MainColumn {
+                followers: count()?,
+            }),
+            "fill" => Some(Pattern::PerMonitor),
+            _ => None,
+        }
+    }
+}
+
+// Generate one window position per slot, relative to the monitor it is on so the layout
+// survives rearranging the monitors. `monitor` indexes `monitors` and is ignored by
+// PerMonitor, which uses all of them.
+pub fn generate(
+    pattern: Pattern,
+    monitors: &[Monitor],
+    monitor: usize,
+) -> Result<Vec<WindowConfig>, String> {
+    let Some(target) = monitors.get(monitor) else {
+        return Err(format!("There is no monitor {}", monitor + 1));
+    };
+    let area = Rect {
+        x: 0,
+        y: 0,
+        ..target.bounds
+    };
+    let rects = match pattern {
+        Pattern::Grid { columns, rows } => grid(area, columns, rows),
+        Pattern::MainStrip { followers } => {
+            let width = area.width / followers as i32;
+            let height = (width * 9 / 16).min(area.height / FOLLOWER_SHARE);
+            let main = Rect {
+                height: area.height - height,
+                ..area
+            };
+            std::iter::once(main)
+                .chain((0..followers as i32).map(|i| Rect {
+                    x: i * width,
+                    y: area.height - height,
+                    width,
+                    height,
+                }))
+                .collect()
+        }
+        Pattern::MainColumn { followers } => {
+            let height = area.height / followers as i32;
+            let width = (height * 16 / 9).min(area.width / FOLLOWER_SHARE);
+            let main = Rect {
+                width: area.width - width,
+                ..area
+            };
+            std::iter::once(main)
+                .chain((0..followers as i32).map(|i| Rect {
+                    x: area.width - width,
+                    y: i * height,
+                    width,
+                    height,
+                }))
+                .collect()
+        }
+        Pattern::PerMonitor => {
+            return Ok(monitors
+                .iter()
+                .enumerate()
+                .map(|(index, monitor)| {
+                    WindowConfig::on_monitor(
+                        Rect {
+                            x: 0,
+                            y: 0,
+                            ..monitor.bounds
+                        },
+                        MonitorRef::Number(index + 1),
+                    )
+                })
+                .collect());
+        }
+    };
+    if let Some(rect) = rects
+        .iter()
+        .find(|rect| rect.width < MIN_WINDOW_SIZE || rect.height < MIN_WINDOW_SIZE)
+    {
+        return Err(format!(
+            "Windows would be {}x{}, smaller than {}x{}",
+            rect.width, rect.height, MIN_WINDOW_SIZE, MIN_WINDOW_SIZE
+        ));
+    }
+    Ok(rects
+        .into_iter()
+        .map(|rect| WindowConfig::on_monitor(rect, MonitorRef::Number(monitor + 1)))
+        .collect())
+}
+
+fn grid(area: Rect, columns: usize, rows: usize) -> Vec<Rect> {
+    let width = area.width / columns as i32;
+    let height = area.height / rows as i32;
+    (0..rows as i32)
+        .flat_map(|row| {
+            (0..columns as i32).map(move |column| Rect {
+                x: area.x + column * width,
+                y: area.y + row * height,
+                width,
+                height,
+            })
+        })
+        .collect()
+}
+
+// Turn generated positions into slot entries, keeping the injection method, character and
+// style of the slots that already exist
+pub fn to_config(positions: Vec<WindowConfig>, existing: &[ConfigFile]) -> Vec<ConfigFile> {
+    positions
+        .into_iter()
+        .enumerate()
+        .map(|(index, position)| {
+            let slot = existing.get(index);
+            ConfigFile {
+                positions: vec![position],
+                injection: slot.map(|slot| slot.injection).unwrap_or_default(),
+                character: slot.and_then(|slot| slot.character.clone()),
+                style: slot.map(|slot| slot.style).unwrap_or_default(),
+                launch: slot.and_then(|slot| slot.launch.clone()),
+            }
+        })
+        .collect()
+}
+
+// Handle "MultiboxSoftware generate <pattern> [monitor <n>] [save]". Prints the generated
+// slots, or writes them to window_config.json with "save". Returns whether it succeeded.
+pub fn run_generate(args: &[String]) -> bool {
+    let usage = || {
+        println!("Usage: MultiboxSoftware generate <grid <columns>x<rows>|strip <followers>|column <followers>|fill> [monitor <n>] [save]");
+        false
+    };
+    let Some(pattern) = Pattern::parse(args) else {
+        return usage();
+    };
+    // Monitors are numbered from 1
+    let monitor = match args.iter().position(|arg| arg == "monitor") {
+        Some(i) => match args.get(i + 1).and_then(|n| n.parse::<usize>().ok()) {
+            Some(number) if number > 0 => number,
+            _ => return usage(),
+        },
+        None => 1,
+    };
+    let save = args.iter().any(|arg| arg == "save");
+
+    let monitors = Win32Monitors.monitors();
+    let positions = match generate(pattern, &monitors, monitor - 1) {
+        Ok(positions) => positions,
+        Err(e) => {
+            println!("{}", e);
+            return false;
+        }
+    };
+    let existing = if save {
+        load_config_file().unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let config = to_config(positions, &existing);
+    if !save {
+        match serde_json::to_string_pretty(&config) {
+            Ok(contents) => println!("{}", contents),
+            Err(e) => println!("Failed to serialize window config: {}", e),
+        }
+        return true;
+    }
+    match save_config_file(&config) {
+        Ok(()) => {
+            println!("Saved {} slots to {}", config.len(), CONFIG_FILE);
+            true
+        }
+        Err(e) => {
+            println!("{}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        layout::{validate_config, Units},
+        monitors::FixedMonitors,
+    };
+
+    // Random monitor setups tried
+    const CASES: usize = 1000;
+
+    // Small deterministic generator, so failures can be reproduced
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn range(&mut self, low: i32, high: i32) -> i32 {
+            low + (self.next() % (high - low + 1) as u64) as i32
+        }
+    }
+
+    // Monitors side by side from left to right, with random sizes, vertical offsets and
+    // scaling
+    fn random_monitors(random: &mut XorShift) -> Vec<Monitor> {
+        const SCALES: [f32; 5] = [1.0, 1.25, 1.5, 1.75, 2.0];
+        let mut x = random.range(-3840, 0);
+        (0..random.range(1, 3))
+            .map(|index| {
+                let bounds = Rect {
+                    x,
+                    y: random.range(-600, 600),
+                    width: random.range(640, 5120),
+                    height: random.range(480, 2880),
+                };
+                x = bounds.right();
+                Monitor {
+                    name: format!("\\\\.\\DISPLAY{}", index + 1),
+                    bounds,
+                    primary: index == 0,
+                    scale: SCALES[random.range(0, SCALES.len() as i32 - 1) as usize],
+                }
+            })
+            .collect()
+    }
+
+    fn random_pattern(random: &mut XorShift) -> Pattern {
+        let count = random.range(1, 8) as usize;
+        match random.range(0, 3) {
+            0 => Pattern::Grid {
+                columns: count,
+                rows: random.range(1, 6) as usize,
+            },
+            1 => Pattern::MainStrip { followers: count },
+            2 => Pattern::MainColumn { followers: count },
+            _ => Pattern::PerMonitor,
+        }
+    }
+
+    // Each window resolves onto the monitor it was generated for, no two windows overlap, the
+    // result passes validation, and the same numbers in logical units survive being moved to
+    // where they resolve to
+    fn check_layout(pattern: Pattern, source: &dyn MonitorSource, monitor: usize) -> Vec<String> {
+        let monitors = source.monitors();
+        let positions = match generate(pattern, &monitors, monitor) {
+            Ok(positions) => positions,
+            // Refusing windows that would be too small is fine
+            Err(_) => return Vec::new(),
+        };
+
+        let mut problems = Vec::new();
+        let expected = match pattern {
+            Pattern::Grid { columns, rows } => columns * rows,
+            Pattern::MainStrip { followers } | Pattern::MainColumn { followers } => followers + 1,
+            Pattern::PerMonitor => monitors.len(),
+        };
+        if positions.len() != expected {
+            problems.push(format!(
+                "{} windows instead of {}",
+                positions.len(),
+                expected
+            ));
+        }
+        let mut rects = Vec::new();
+        for (index, position) in positions.iter().enumerate() {
+            match position.resolve(&monitors) {
+                Ok(rect) => rects.push(rect),
+                Err(e) => problems.push(format!("OMB {}: {}", index + 1, e)),
+            }
+
+            let logical = WindowConfig {
+                units: Units::Logical,
+                ..position.clone()
+            };
+            if let Ok(rect) = logical.resolve(&monitors) {
+                let mut moved = logical.clone();
+                moved.set_rect(rect, &monitors);
+                if moved != logical {
+                    problems.push(format!(
+                        "OMB {} {:?} became {:?} in logical units",
+                        index + 1,
+                        logical,
+                        moved
+                    ));
+                }
+            }
+        }
+        for (index, rect) in rects.iter().enumerate() {
+            let bounds = match pattern {
+                Pattern::PerMonitor => monitors[index].bounds,
+                _ => monitors[monitor].bounds,
+            };
+            if !bounds.contains(rect) {
+                problems.push(format!(
+                    "OMB {} {:?} is outside {:?}",
+                    index + 1,
+                    rect,
+                    bounds
+                ));
+            }
+            for (other_index, other) in rects.iter().enumerate().skip(index + 1) {
+                if rect.intersects(other) {
+                    problems.push(format!(
+                        "OMB {} overlaps OMB {}",
+                        index + 1,
+                        other_index + 1
+                    ));
+                }
+            }
+        }
+        problems.extend(validate_config(&to_config(positions, &[]), &monitors));
+        problems
+    }
+
+    #[test]
+    fn generated_layouts_fit_random_monitors() {
+        let mut random = XorShift(0x9E37_79B9_7F4A_7C15);
+        for case in 0..CASES {
+            let source = FixedMonitors(random_monitors(&mut random));
+            let pattern = random_pattern(&mut random);
+            let monitor = random.range(0, source.0.len() as i32 - 1) as usize;
+            let problems = check_layout(pattern, &source, monitor);
+            assert!(
+                problems.is_empty(),
+                "case {}: {:?} on monitor {} of {:?}:\n{}",
+                case + 1,
+                pattern,
+                monitor + 1,
+                source.0,
+                problems.join("\n")
+            );
+        }
+    }
+
+    #[test]
+    fn parses_patterns() {
+        let args = |args: &str| args.split(' ').map(String::from).collect::<Vec<_>>();
+        assert_eq!(
+            Pattern::parse(&args("grid 3x2")),
+            Some(Pattern::Grid {
+                columns: 3,
+                rows: 2
+            })
+        );
+        assert_eq!(
+            Pattern::parse(&args("strip 4")),
+            Some(Pattern::MainStrip { followers: 4 })
+        );
+        assert_eq!(Pattern::parse(&args("fill")), Some(Pattern::PerMonitor));
+        assert_eq!(Pattern::parse(&args("grid 0x2")), None);
+        assert_eq!(Pattern::parse(&args("column 0")), None);
+        assert_eq!(Pattern::parse(&args("strip")), None);
+    }
+}
@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hotkeys::parse_key_name;
+
+// How often the leader is re-evaluated
+pub const LEADER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Which window leads the group
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderMode {
+    // The focused game window. Focusing something else keeps the last leader.
+    #[default]
+    Foreground,
+    // The window in layout slot 1, which changes with swaps
+    MainSlot,
+    // Always the given OMB number
+    Fixed(usize),
+}
+
+// Leader settings from settings.json, e.g.
+// { "mode": "foreground", "follow_keys": ["INSERT", "HOME", "PAGEUP"] }
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeaderSettings {
+    pub mode: LeaderMode,
+    // Key tapped in every other window when OMB N becomes leader, entry N - 1.
+    // Bind each in game to a follow or assist macro for that character.
+    pub follow_keys: Vec<String>,
+}
+
+impl LeaderSettings {
+    // Virtual-key code of the follow key for a leader, if one is configured
+    pub fn follow_key(&self, leader: usize) -> Option<u16> {
+        let name = self.follow_keys.get(leader.checked_sub(1)?)?;
+        let vk = parse_key_name(name);
+        if vk.is_none() {
+            println!("Unknown follow key name: {}", name);
+        }
+        vk
+    }
+}
+
+// Pick the leader.
+// `foreground` is the OMB number of the focused window, None when no game window is focused.
+// `main_slot` is the OMB number of the window in layout slot 1.
+// `open` are the OMB numbers of the open game windows. When the window the mode asks for
+// isn't open, the current leader stays on if its window is, or else the lowest open number
+// leads. None when no game window is open.
+pub fn elect_leader(
+    mode: LeaderMode,
+    current: Option<usize>,
+    foreground: Option<usize>,
+    main_slot: usize,
+    open: &[usize],
+) -> Option<usize> {
+    let wanted = match mode {
+        LeaderMode::Foreground => foreground,
+        LeaderMode::MainSlot => Some(main_slot),
+        LeaderMode::Fixed(number) => Some(number),
+    };
+    let is_open = |number: &usize| open.contains(number);
+    wanted
+        .filter(is_open)
+        .or(current.filter(is_open))
+        .or_else(|| open.iter().min().copied())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn foreground_window_leads() {
+        let open = [1, 2, 3];
+        let elect =
+            |foreground| elect_leader(LeaderMode::Foreground, Some(1), foreground, 1, &open);
+        assert_eq!(elect(Some(2)), Some(2));
+        // Focusing something else keeps the last leader
+        assert_eq!(elect(None), Some(1));
+    }
+
+    #[test]
+    fn main_slot_window_leads() {
+        let leader = elect_leader(LeaderMode::MainSlot, Some(1), Some(2), 3, &[1, 2, 3]);
+        assert_eq!(leader, Some(3));
+    }
+
+    #[test]
+    fn fixed_window_leads() {
+        let leader = elect_leader(LeaderMode::Fixed(2), None, Some(3), 1, &[1, 2, 3]);
+        assert_eq!(leader, Some(2));
+    }
+
+    #[test]
+    fn current_leader_stays_when_the_configured_one_is_missing() {
+        let open = [1, 3];
+        assert_eq!(
+            elect_leader(LeaderMode::Fixed(2), Some(3), None, 1, &open),
+            Some(3)
+        );
+        assert_eq!(
+            elect_leader(LeaderMode::MainSlot, Some(3), None, 2, &open),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn lowest_open_window_leads_when_the_configured_one_is_missing() {
+        let open = [4, 3];
+        assert_eq!(
+            elect_leader(LeaderMode::Fixed(2), None, None, 1, &open),
+            Some(3)
+        );
+        // The current leader closed as well
+        assert_eq!(
+            elect_leader(LeaderMode::Fixed(2), Some(1), None, 1, &open),
+            Some(3)
+        );
+        assert_eq!(
+            elect_leader(LeaderMode::MainSlot, Some(2), None, 1, &open),
+            Some(3)
+        );
+        assert_eq!(
+            elect_leader(LeaderMode::Foreground, Some(1), None, 1, &open),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn configured_leader_takes_over_again_when_it_opens() {
+        let leader = elect_leader(LeaderMode::Fixed(2), Some(3), None, 1, &[1, 2, 3]);
+        assert_eq!(leader, Some(2));
+    }
+
+    #[test]
+    fn no_leader_without_windows() {
+        for mode in [
+            LeaderMode::Foreground,
+            LeaderMode::MainSlot,
+            LeaderMode::Fixed(1),
+        ] {
+            assert_eq!(
+                elect_leader(mode, Some(1), Some(1), 1, &[]),
+                None,
+                "{:?}",
+                mode
+            );
+        }
+    }
+}
@@ -1,57 +1,92 @@
 use std::{
-    collections::{HashMap, HashSet},
-    sync::{Arc, LazyLock, Mutex},
-    thread::sleep,
+    collections::HashMap,
+    sync::{atomic::Ordering, Arc, LazyLock},
+    thread::{sleep, JoinHandle},
     time::{Duration, Instant},
 };
 
-use serde::{Deserialize, Serialize};
-
-use windows::{
-    core::*,
-    Win32::{
-        Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, RECT, WPARAM},
-        Graphics::Gdi::{
-            CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC, GetPixel,
-            ReleaseDC, SelectObject, HBITMAP, HDC, HGDIOBJ,
+mod app;
+mod control;
+mod crash;
+mod dashboard;
+mod discovery;
+mod health;
+mod hotkeys;
+mod injector;
+mod keystate;
+mod launcher;
+mod layout;
+mod layout_editor;
+mod layout_generator;
+mod leader;
+mod monitors;
+mod movement;
+mod pin;
+mod pipeline;
+mod protocol;
+mod recorder;
+mod registry;
+mod settings;
+#[cfg(test)]
+mod simulator;
+mod slots;
+mod store;
+mod titles;
+mod win32_injector;
+mod win32_monitors;
+mod win32_windows;
+mod window_style;
+
+use app::AppState;
+use crash::{save_crashes, CrashCause};
+use discovery::{discover, Discovered};
+use health::{
+    save_window_status, Observation, Transition, WindowHealth, WindowState, WindowStatus,
+};
+use hotkeys::{find_hotkey, HotkeyAction, ModifierState};
+use injector::{InjectionMethod, KeyInjector};
+use keystate::KeyStateTracker;
+use launcher::LaunchSettings;
+use layout::{ConfigFile, Rect, WindowConfig, WindowStyle};
+use leader::{elect_leader, LeaderSettings, LEADER_POLL_INTERVAL};
+use monitors::MonitorSource;
+use pin::{PinSettings, PinState};
+use pipeline::{ControlHandler, Frame, FrameOutcome, WindowPipeline};
+use protocol::{ControlCommand, Feedback, MovementPixel, SENTINEL_COLOR};
+use recorder::{KeyRecorder, RecordedEvent, WindowRecording};
+use store::WindowEntry;
+use titles::{render_title, TitleInfo, DEFAULT_TITLE_TEMPLATE};
+use win32_injector::create_injector;
+use win32_monitors::{enable_dpi_awareness, Win32Monitors};
+use win32_windows::{window_process_id, ProcessExit, ProcessHandle, Win32Windows};
+use window_style::{apply_window_style, restore_window_styles};
+
+use windows::Win32::{
+    Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, RECT, WPARAM},
+    Graphics::Gdi::{
+        CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC, GetPixel,
+        ReleaseDC, SelectObject, HBITMAP, HDC, HGDIOBJ,
+    },
+    Storage::Xps::{PrintWindow, PW_CLIENTONLY},
+    System::{LibraryLoader::GetModuleHandleW, Threading::GetCurrentThreadId},
+    UI::{
+        Input::KeyboardAndMouse::{
+            GetAsyncKeyState, VIRTUAL_KEY, VK_CONTROL, VK_LBUTTON, VK_MENU, VK_SHIFT,
         },
-        Storage::Xps::{PrintWindow, PW_CLIENTONLY},
-        System::LibraryLoader::GetModuleHandleW,
-        UI::{
-            Input::KeyboardAndMouse::{
-                VIRTUAL_KEY, VK_A, VK_D, VK_DOWN, VK_F1, VK_F10, VK_F11, VK_F12, VK_F13, VK_F14,
-                VK_F15, VK_F16, VK_F17, VK_F18, VK_F19, VK_F2, VK_F20, VK_F21, VK_F22, VK_F23,
-                VK_F24, VK_F3, VK_F4, VK_F5, VK_F6, VK_F7, VK_F8, VK_F9, VK_LCONTROL, VK_LEFT,
-                VK_LMENU, VK_LSHIFT, VK_NUMPAD0, VK_RIGHT, VK_UP,
-            },
-            WindowsAndMessaging::{
-                CallNextHookEx, DispatchMessageW, EnumWindows, GetForegroundWindow, GetMessageW,
-                GetWindowRect, GetWindowTextLengthW, GetWindowTextW, IsWindow, PostMessageW,
-                SetWindowPos, SetWindowTextW, SetWindowsHookExW, TranslateMessage,
-                UnhookWindowsHookEx, HWND_TOP, KBDLLHOOKSTRUCT, LLKHF_INJECTED, MSG, SWP_NOZORDER,
-                WH_KEYBOARD_LL, WM_KEYDOWN, WM_KEYUP,
-            },
+        WindowsAndMessaging::{
+            CallNextHookEx, DispatchMessageW, GetForegroundWindow, GetMessageW, GetWindowRect,
+            GetWindowTextLengthW, GetWindowTextW, IsIconic, IsWindow, PostMessageW,
+            PostThreadMessageW, SendMessageTimeoutW, SetForegroundWindow, SetWindowPos,
+            SetWindowsHookExW, ShowWindow, TranslateMessage, UnhookWindowsHookEx, HWND_TOP,
+            KBDLLHOOKSTRUCT, LLKHF_INJECTED, MSG, SMTO_ABORTIFHUNG, SWP_NOZORDER, SW_RESTORE,
+            WH_KEYBOARD_LL, WM_KEYDOWN, WM_KEYUP, WM_QUIT, WM_SETTEXT, WM_SYSKEYDOWN, WM_SYSKEYUP,
         },
     },
 };
 
-// Configuration structs for window positions and sizes
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct WindowConfig {
-    x: i32,
-    y: i32,
-    width: i32,
-    height: i32,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ConfigFile {
-    positions: Vec<WindowConfig>,
-}
-
 // Define a wrapper type for HWND to make it hashable
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct HwndWrapper(HWND);
+pub struct HwndWrapper(HWND);
 
 impl std::hash::Hash for HwndWrapper {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
@@ -61,178 +96,274 @@ impl std::hash::Hash for HwndWrapper {
 
 // Manually implement Send for HwndWrapper
 unsafe impl Send for HwndWrapper {}
+// Injectors hold the HWND and are shared between threads
+unsafe impl Sync for HwndWrapper {}
 
 const PIXEL_X: i32 = 0;
 const PIXEL_Y: i32 = 0;
 const SENTINEL_X: i32 = 1;
 const MOVEMENT_ROTATION_PIXEL_X: i32 = 2; // New pixel for movement/rotation
-                                          // In-game addon sets sentinel pixels to check if the addon is active.
-                                          // Lua (r=0x12, g=0x34, b=0x56) -> BGR 0x563412
-const SENTINEL_COLOR: u32 = 0x563412;
-
-// Global configuration storage
-static mut CONFIG: Option<Vec<ConfigFile>> = None;
-
-static HWND_SET: LazyLock<Mutex<HashSet<HwndWrapper>>> =
-    LazyLock::new(|| Mutex::new(HashSet::new()));
-
-static BROADCAST_ENABLED: LazyLock<Mutex<bool>> = LazyLock::new(|| Mutex::new(false));
-
-// Load configuration from JSON file
-fn load_config() {
-    match std::fs::read_to_string("window_config.json") {
-        Ok(contents) => match serde_json::from_str::<Vec<ConfigFile>>(&contents) {
-            Ok(config) => unsafe {
-                CONFIG = Some(config);
-            },
-            Err(e) => println!("Failed to parse JSON: {}", e),
-        },
-        Err(e) => println!("Failed to read config file: {}", e),
-    }
-}
 
-// Get window configuration by index
+// How long a window gets to take a new title before it counts as hung
+const SET_TITLE_TIMEOUT_MS: u32 = 500;
+
+// How often started clients are checked for having exited
+const CRASH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// How long a client whose window closed in game gets to exit, before it counts as crashed
+// without an exit code
+const CLIENT_EXIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// State shared by every thread of the running instance, see app.rs
+static APP: LazyLock<AppState> = LazyLock::new(AppState::load);
+
+// Get window configuration by index for the active layout.
+// Slots with fewer positions than the active layout fall back to their first one.
 fn get_window_config(index: usize) -> Option<&'static WindowConfig> {
-    unsafe {
-        if let Some(ref config) = CONFIG {
-            if index < config.len() {
-                return config[index].positions.first();
-            }
-        }
-    }
-    None
+    let positions = &APP.config.as_ref()?.get(index)?.positions;
+    positions
+        .get(APP.active_layout.load(Ordering::SeqCst))
+        .or(positions.first())
+}
+
+// Get the window configuration of the layout slot an OMB window is assigned to
+fn get_slot_config(omb_number: usize) -> Option<&'static WindowConfig> {
+    let slot = APP.slot_assignments.lock().unwrap().slot_of(omb_number);
+    slot.checked_sub(1).and_then(get_window_config)
+}
+
+// Number of layouts, i.e. the most positions any slot has
+fn layout_count() -> usize {
+    APP.config
+        .iter()
+        .flatten()
+        .map(|slot| slot.positions.len())
+        .max()
+        .unwrap_or(1)
+        .max(1)
+}
+
+// Get the slot configured for a slot index
+fn get_config_slot(index: usize) -> Option<&'static ConfigFile> {
+    APP.config.as_ref()?.get(index)
 }
 
-// Find the lowest available OMB number
-fn find_lowest_omb_number() -> Option<usize> {
-    let mut used_numbers = HashSet::new();
-    let mut to_remove = Vec::new();
+// Get the key injection strategy configured for a slot index
+fn get_injection_method(index: usize) -> InjectionMethod {
+    get_config_slot(index)
+        .map(|slot| slot.injection)
+        .unwrap_or_default()
+}
+
+// Get the window style of the layout slot an OMB window is assigned to
+fn get_slot_style(omb_number: usize) -> WindowStyle {
+    let slot = APP.slot_assignments.lock().unwrap().slot_of(omb_number);
+    slot.checked_sub(1)
+        .and_then(get_config_slot)
+        .map(|slot| slot.style)
+        .unwrap_or_default()
+}
+
+// Get the character name configured for a slot index
+fn get_character(index: usize) -> Option<String> {
+    get_config_slot(index)?.character.clone()
+}
 
-    // Scope the lock to avoid deadlock when removing
+// All registered game windows with their OMB numbers
+fn omb_windows() -> Vec<(usize, HWND)> {
+    APP.registry
+        .lock()
+        .unwrap()
+        .windows()
+        .into_iter()
+        .map(|(number, hwnd)| (number, hwnd.0))
+        .collect()
+}
+
+fn find_window_by_omb_number(number: usize) -> Option<HWND> {
+    APP.registry
+        .lock()
+        .unwrap()
+        .window_of(number)
+        .map(|hwnd| hwnd.0)
+}
+
+fn find_omb_number_by_window(hwnd: HWND) -> Option<usize> {
+    APP.registry.lock().unwrap().number_of(HwndWrapper(hwnd))
+}
+
+// Swap the layout slots of two OMB windows, remember the swap and move both windows.
+// Titles and OMB numbers stay with their game clients.
+fn swap_slots(omb_a: usize, omb_b: usize) {
     {
-        let set = HWND_SET.lock().unwrap();
-        for hwnd in set.iter() {
-            unsafe {
-                if !IsWindow(Some(hwnd.0)).as_bool() {
-                    to_remove.push(*hwnd);
-                    continue;
-                }
+        let mut assignments = APP.slot_assignments.lock().unwrap();
+        assignments.swap(omb_a, omb_b);
+        assignments.save();
+    }
+    println!("Swapped layout slots of OMB {} and OMB {}", omb_a, omb_b);
+
+    let hwnd_a = find_window_by_omb_number(omb_a);
+    let hwnd_b = find_window_by_omb_number(omb_b);
+    match (get_slot_config(omb_a), get_slot_config(omb_b)) {
+        (Some(config_a), Some(config_b)) => {
+            if let Some(hwnd) = hwnd_a {
+                set_window_position(hwnd, config_a, get_slot_style(omb_a));
             }
-            let (title_str, number) = get_window_title_and_omb_number(hwnd.0);
-            if title_str.starts_with("OMB ") && Some(number).is_some() {
-                println!("Found used OMB number: {}", title_str);
-                used_numbers.insert(number.unwrap());
+            if let Some(hwnd) = hwnd_b {
+                set_window_position(hwnd, config_b, get_slot_style(omb_b));
             }
         }
-    } // Lock released here
-
-    // Remove dead windows safely
-    if !to_remove.is_empty() {
-        let mut set = HWND_SET.lock().unwrap();
-        for hwnd in to_remove {
-            set.remove(&hwnd);
+        // Slots without a configured position can only trade their current rectangles
+        _ => {
+            if let (Some(hwnd_a), Some(hwnd_b)) = (hwnd_a, hwnd_b) {
+                swap_window_positions(hwnd_a, hwnd_b);
+            }
         }
     }
+}
 
-    // Find the smallest number not in use
-    for i in 1.. {
-        if !used_numbers.contains(&i) {
-            return Some(i);
-        }
+// Move an OMB window into the main layout slot, putting the window there in its place
+fn swap_into_main_slot(omb_number: usize) {
+    let main_omb_number = APP.slot_assignments.lock().unwrap().omb_in(1);
+    if main_omb_number != omb_number {
+        swap_slots(omb_number, main_omb_number);
     }
-    None
 }
 
-// Set window position and size
-fn set_window_position(hwnd: HWND, config: &WindowConfig) {
+// The window rectangle a slot position asks for.
+// Monitors can change while the tool runs, so positions are resolved every time.
+fn placement_rect(config: &WindowConfig) -> std::result::Result<Rect, String> {
+    config.resolve(&Win32Monitors.monitors())
+}
+
+// Set window position, size and style
+fn set_window_position(hwnd: HWND, config: &WindowConfig, style: WindowStyle) {
+    apply_window_style(hwnd, style);
+    let rect = match placement_rect(config) {
+        Ok(rect) => rect,
+        Err(e) => {
+            println!("Not moving window: {}", e);
+            return;
+        }
+    };
     unsafe {
         let _ = SetWindowPos(
             hwnd,
             Some(HWND_TOP),
-            config.x,
-            config.y,
-            1280, //currently hardcoded to always match in game resolution of 1280x720
-            720,
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
             SWP_NOZORDER,
         );
     }
 }
 
-// Rename window to OMB format
-fn rename_window(hwnd: HWND, new_title: &str) {
+fn set_window_title(hwnd: HWND, title: &str) {
     unsafe {
-        let mut title_wide: Vec<u16> = new_title.encode_utf16().collect();
+        let mut title_wide: Vec<u16> = title.encode_utf16().collect();
         title_wide.push(0);
-        let _ = SetWindowTextW(hwnd, windows::core::PCWSTR::from_raw(title_wide.as_ptr()));
-        println!("Renamed window to: {}", new_title);
+        // SetWindowTextW waits for the game to handle the message, which never happens
+        // while it hangs. Titles change when a window goes stale, so give up instead.
+        let _ = SendMessageTimeoutW(
+            hwnd,
+            WM_SETTEXT,
+            WPARAM(0),
+            LPARAM(title_wide.as_ptr() as isize),
+            SMTO_ABORTIFHUNG,
+            SET_TITLE_TIMEOUT_MS,
+            None,
+        );
     }
 }
 
-// Static mutable vector to collect HWNDs during window enumeration
-static mut HWNDS: Vec<HWND> = vec![];
-
-// Helper function to get all windows with a specific title
-fn find_all_windows_with_title() -> Vec<HWND> {
-    unsafe {
-        // Clear the static vector before enumerating
-        HWNDS.clear();
+// Rename window to OMB format
+fn rename_window(hwnd: HWND, new_title: &str) {
+    let original_title = get_window_title(hwnd);
+    APP.original_titles
+        .lock()
+        .unwrap()
+        .entry(HwndWrapper(hwnd))
+        .or_insert(original_title);
+
+    set_window_title(hwnd, new_title);
+    println!("Renamed window to: {}", new_title);
+}
 
-        let _ = EnumWindows(Some(enum_window_callback), LPARAM(0));
+// The title a window in an OMB slot should have right now
+fn slot_title(omb_number: usize) -> String {
+    let window = APP.registry.lock().unwrap().window_of(omb_number);
+    let state = window
+        .and_then(|window| {
+            APP.window_status
+                .lock()
+                .unwrap()
+                .get(&window)
+                .map(|entry| entry.status.state)
+        })
+        .unwrap_or(WindowState::Discovered);
+    let character = get_character(omb_number - 1);
+    let info = TitleInfo {
+        omb_number,
+        character: character.as_deref(),
+        state,
+        paused: APP.automation_paused.load(Ordering::SeqCst),
+    };
+    let template = APP
+        .settings
+        .title_template
+        .as_deref()
+        .unwrap_or(DEFAULT_TITLE_TEMPLATE);
+    render_title(template, &info)
+}
 
-        // Return a copy of collected HWNDs
-        HWNDS.clone()
+// Bring a window's title up to date with its slot's character and status
+fn update_window_title(hwnd: HWND, omb_number: usize) {
+    let title = slot_title(omb_number);
+    if get_window_title(hwnd) != title {
+        set_window_title(hwnd, &title);
     }
 }
 
-// Callback for EnumWindows to collect hwnds matching the target title
-unsafe extern "system" fn enum_window_callback(hwnd: HWND, _: LPARAM) -> BOOL {
-    let len = GetWindowTextLengthW(hwnd);
-
-    if len > 0 {
-        let mut title_buf = vec![0u16; (len + 1) as usize];
-        GetWindowTextW(hwnd, &mut title_buf);
-
-        let title_str = String::from_utf16_lossy(&title_buf);
-
-        // Handle "OMB" prefix windows
-        if title_str.starts_with("OMB ") {
-            HWNDS.push(hwnd);
-            if HWND_SET.lock().unwrap().contains(&HwndWrapper(hwnd)) {
-                return windows::core::BOOL::from(true);
-            }
-            let num_str_opt = title_str
-                .strip_prefix("OMB ")
-                .and_then(|s| s.strip_suffix("\0"));
-            // Extract the number from "OMB X" format
-            if let Some(num_str) = num_str_opt {
-                if let Ok(index) = num_str.parse::<usize>() {
-                    if let Some(config) = get_window_config(index - 1) {
-                        set_window_position(hwnd, config);
-                    }
-                } else {
-                    let err = num_str.parse::<usize>();
-                    println!("Failed to parse OMB number from title: {:?}", err);
-                }
+// Put back the titles of every window renamed by rename_window
+fn restore_window_titles() {
+    let titles = std::mem::take(&mut *APP.original_titles.lock().unwrap());
+    for (hwnd, title) in titles {
+        unsafe {
+            if IsWindow(Some(hwnd.0)).as_bool() {
+                set_window_title(hwnd.0, &title);
+                println!("Restored window title: {}", title);
             }
         }
-        // Handle "World of Warcraft" windows - rename to lowest available OMB number
-        else if title_str == "World of Warcraft\0" {
-            if let Some(lowest_num) = find_lowest_omb_number() {
-                let new_title = format!("OMB {}", lowest_num);
-                rename_window(hwnd, &new_title);
-
-                // Apply position configuration for the new OMB number
-                if let Some(config) = get_window_config(lowest_num - 1) {
-                    println!("Applying config for {}", new_title);
-                    set_window_position(hwnd, config);
-                }
+    }
+}
 
-                HWNDS.push(hwnd);
-            }
-        }
+// Ask every thread to stop. Safe to call from any thread, any number of times.
+fn request_shutdown() {
+    if !APP.shutdown.swap(true, Ordering::SeqCst) {
+        println!("Shutting down...");
     }
+    // Wake the message loop out of GetMessageW
+    unsafe {
+        let _ = PostThreadMessageW(
+            APP.message_loop_thread_id.load(Ordering::SeqCst),
+            WM_QUIT,
+            WPARAM(0),
+            LPARAM(0),
+        );
+    }
+}
+
+fn is_shutting_down() -> bool {
+    APP.shutdown.load(Ordering::SeqCst)
+}
 
-    windows::core::BOOL::from(true)
+// Block until main has finished cleaning up, or the timeout expires
+fn wait_for_shutdown(timeout: Duration) {
+    let start = Instant::now();
+    while !APP.shutdown_complete.load(Ordering::SeqCst) && start.elapsed() < timeout {
+        sleep(Duration::from_millis(50));
+    }
 }
 
 unsafe extern "system" fn keyboard_hook_proc(
@@ -250,21 +381,45 @@ unsafe extern "system" fn keyboard_hook_proc(
             return CallNextHookEx(None, n_code, w_param, l_param);
         }
 
+        // Global hotkeys work whether or not the addon is loaded
+        if event_type == WM_KEYDOWN || event_type == WM_SYSKEYDOWN {
+            // Holding a hotkey repeats its key down, the action runs once per press
+            if APP.swallowed_key.load(Ordering::SeqCst) == vk_code {
+                return LRESULT(1);
+            }
+            if let Some(action) = find_hotkey(&APP.hotkeys, vk_code as u16, current_modifiers()) {
+                APP.swallowed_key.store(vk_code, Ordering::SeqCst);
+                // Keep the hook fast, actions may move or focus windows
+                std::thread::spawn(move || run_hotkey_action(action, "Hotkey"));
+                return LRESULT(1);
+            }
+        } else if (event_type == WM_KEYUP || event_type == WM_SYSKEYUP)
+            && APP
+                .swallowed_key
+                .compare_exchange(vk_code, 0, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        {
+            return LRESULT(1);
+        }
+
         // Only handle keydown and keyup events
         if event_type != WM_KEYDOWN && event_type != WM_KEYUP {
             return CallNextHookEx(None, n_code, w_param, l_param);
         }
 
-        if *BROADCAST_ENABLED.lock().unwrap() {
+        if *APP.broadcast_enabled.lock().unwrap() {
             let foreground_hwnd = GetForegroundWindow();
-            let wow_windows = HWND_SET.lock().unwrap();
-
-            if wow_windows.contains(&HwndWrapper(foreground_hwnd)) {
-                for &window in wow_windows.iter() {
-                    if window.0 != foreground_hwnd {
+            let wow_windows = omb_windows();
+
+            if wow_windows
+                .iter()
+                .any(|&(_, window)| window == foreground_hwnd)
+            {
+                for &(_, window) in wow_windows.iter() {
+                    if window != foreground_hwnd {
                         println!(
                             "Broadcasting key {}, event {} to window {:?}",
-                            vk_code, event_type, window.0
+                            vk_code, event_type, window
                         );
                         let mut l_param = 1;
                         if event_type == WM_KEYUP {
@@ -272,11 +427,19 @@ unsafe extern "system" fn keyboard_hook_proc(
                             l_param = l_param | 0xC0000000;
                         }
                         let _ = PostMessageW(
-                            Some(window.0),
+                            Some(window),
                             event_type,
                             WPARAM(vk_code as usize),
                             LPARAM(l_param),
                         );
+                        if let Some(recording) =
+                            APP.recordings.lock().unwrap().get(&HwndWrapper(window))
+                        {
+                            recording.record(RecordedEvent::Broadcast {
+                                vk: vk_code as u16,
+                                down: event_type == WM_KEYDOWN,
+                            });
+                        }
                     }
                 }
             }
@@ -286,67 +449,417 @@ unsafe extern "system" fn keyboard_hook_proc(
     CallNextHookEx(None, n_code, w_param, l_param)
 }
 
+// Modifiers currently held on the physical keyboard
+fn current_modifiers() -> ModifierState {
+    let is_down = |vk: VIRTUAL_KEY| unsafe { GetAsyncKeyState(vk.0 as i32) } < 0;
+    ModifierState {
+        ctrl: is_down(VK_CONTROL),
+        shift: is_down(VK_SHIFT),
+        alt: is_down(VK_MENU),
+    }
+}
+
+// Run a hotkey action, also used by the dashboard's buttons
+fn run_hotkey_action(action: HotkeyAction, source: &str) {
+    match action {
+        HotkeyAction::ToggleBroadcast => {
+            let enabled = !*APP.broadcast_enabled.lock().unwrap();
+            set_broadcast_enabled(enabled, source);
+        }
+        HotkeyAction::TogglePause => {
+            let paused = !APP.automation_paused.fetch_xor(true, Ordering::SeqCst);
+            println!(
+                "[{}] Automation {}",
+                source,
+                if paused { "paused" } else { "resumed" }
+            );
+            for (number, hwnd) in omb_windows() {
+                update_window_title(hwnd, number);
+            }
+        }
+        HotkeyAction::CycleLayout => cycle_layout(),
+        HotkeyAction::SwapWithMain => swap_focused_with_main(),
+        HotkeyAction::FocusSlot(number) => {
+            focus_slot(number, false);
+        }
+        HotkeyAction::FocusSlotAsMain(number) => {
+            focus_slot(number, true);
+        }
+        // Launching waits between clients, keep that out of the keyboard hook
+        HotkeyAction::LaunchMissing => {
+            std::thread::spawn(launch_missing_clients);
+        }
+    }
+}
+
+// Count a crash of a slot's client, publish it and queue the client to be started again
+// when settings.json asks for that
+fn report_crash(omb_number: usize, process_id: u32, cause: CrashCause) {
+    let mut crashes = APP.crashes.lock().unwrap();
+    if !crashes.report(omb_number, process_id, cause, Instant::now()) {
+        return;
+    }
+    println!(
+        "[OMB {}] Client crashed: {}, {} crashes since startup",
+        omb_number,
+        cause,
+        crashes.count(omb_number)
+    );
+    let can_relaunch = get_config_slot(omb_number - 1).is_some_and(|slot| slot.launch.is_some());
+    if let Some(launch) = APP.settings.launch.as_ref() {
+        if launch.relaunch_crashed && can_relaunch {
+            if crashes.queue_relaunch(omb_number, launch.max_relaunches) {
+                println!("[OMB {}] Relaunching the client", omb_number);
+            } else {
+                println!(
+                    "[OMB {}] Not relaunching, it was relaunched {} times already",
+                    omb_number, launch.max_relaunches
+                );
+            }
+        }
+    }
+    save_crashes(&crashes.slots());
+}
+
+// Notice clients the tool started exiting, and start crashed ones again once their window
+// and process are gone
+fn run_crash_monitor() {
+    while !is_shutting_down() {
+        let exited = APP.launcher.lock().unwrap().reap();
+        for (omb_number, process_id, status) in exited {
+            match status.code() {
+                Some(code) if code != 0 => {
+                    report_crash(omb_number, process_id, CrashCause::ExitCode(code))
+                }
+                _ => println!("[OMB {}] Client exited", omb_number),
+            }
+        }
+
+        let pending = APP.crashes.lock().unwrap().pending_relaunches();
+        for omb_number in pending {
+            let Some(launch) =
+                get_config_slot(omb_number - 1).and_then(|slot| slot.launch.as_ref())
+            else {
+                continue;
+            };
+            if find_window_by_omb_number(omb_number).is_some() {
+                continue;
+            }
+            let result = {
+                let mut launcher = APP.launcher.lock().unwrap();
+                if launcher.is_running(omb_number) {
+                    continue;
+                }
+                launcher.launch(omb_number, launch)
+            };
+            APP.crashes.lock().unwrap().relaunched(omb_number);
+            match result {
+                Ok(process_id) => println!(
+                    "[OMB {}] Started {} again as process {}",
+                    omb_number, launch.exe, process_id
+                ),
+                Err(e) => println!("[OMB {}] {}", omb_number, e),
+            }
+        }
+        sleep(CRASH_CHECK_INTERVAL);
+    }
+}
+
+// The OMB number a window's client was started for, if the tool started it
+fn launched_slot(window: HwndWrapper) -> Option<usize> {
+    let process_id = window_process_id(window.0);
+    APP.launcher.lock().unwrap().slot_of_process(process_id)
+}
+
+// Start the client of every slot with a "launch" entry that has no window and no client
+// starting up
+fn launch_missing_clients() {
+    let Some(config) = APP.config.as_ref() else {
+        return;
+    };
+    let delay = APP
+        .settings
+        .launch
+        .as_ref()
+        .map_or(LaunchSettings::default().delay(), LaunchSettings::delay);
+    for (index, slot) in config.iter().enumerate() {
+        let omb_number = index + 1;
+        let Some(launch) = &slot.launch else {
+            continue;
+        };
+        if is_shutting_down() || find_window_by_omb_number(omb_number).is_some() {
+            continue;
+        }
+        let result = {
+            let mut launcher = APP.launcher.lock().unwrap();
+            if launcher.is_running(omb_number) {
+                continue;
+            }
+            launcher.launch(omb_number, launch)
+        };
+        match result {
+            Ok(process_id) => {
+                println!(
+                    "[OMB {}] Started {} as process {}",
+                    omb_number, launch.exe, process_id
+                );
+                sleep(delay);
+            }
+            Err(e) => println!("[OMB {}] {}", omb_number, e),
+        }
+    }
+}
+
+// Switch to the next layout and move every window into it
+fn cycle_layout() {
+    let layout = (APP.active_layout.load(Ordering::SeqCst) + 1) % layout_count();
+    APP.active_layout.store(layout, Ordering::SeqCst);
+    println!("Switched to layout {}", layout + 1);
+
+    for (number, hwnd) in omb_windows() {
+        if let Some(config) = get_slot_config(number) {
+            set_window_position(hwnd, config, get_slot_style(number));
+        }
+    }
+}
+
+// Swap the focused game window with the window in the main layout slot
+fn swap_focused_with_main() {
+    let foreground_hwnd = unsafe { GetForegroundWindow() };
+    match find_omb_number_by_window(foreground_hwnd) {
+        Some(number) => swap_into_main_slot(number),
+        None => println!("Focused window is not a game window, not swapping"),
+    }
+}
+
+// Bring the window registered for an OMB number to the foreground,
+// optionally swapping it into the main layout slot first. Returns whether it was focused.
+fn focus_slot(number: usize, into_main: bool) -> bool {
+    let Some(hwnd) = find_window_by_omb_number(number) else {
+        println!("No window registered as OMB {}", number);
+        return false;
+    };
+    if into_main {
+        swap_into_main_slot(number);
+    }
+    unsafe {
+        if IsIconic(hwnd).as_bool() {
+            let _ = ShowWindow(hwnd, SW_RESTORE);
+        }
+        if !SetForegroundWindow(hwnd).as_bool() {
+            println!("Failed to focus OMB {}", number);
+            return false;
+        }
+    }
+    println!("Focused OMB {}", number);
+    true
+}
+
+// Keep track of the leader and tell the other windows to follow it whenever it changes
+fn run_leader_election(leader_settings: &LeaderSettings) {
+    while !is_shutting_down() {
+        let current = Some(APP.leader.load(Ordering::SeqCst)).filter(|&number| number != 0);
+        let foreground = find_omb_number_by_window(unsafe { GetForegroundWindow() });
+        let main_slot = APP.slot_assignments.lock().unwrap().omb_in(1);
+        let open: Vec<usize> = omb_windows()
+            .into_iter()
+            .map(|(number, _)| number)
+            .collect();
+
+        let elected = elect_leader(leader_settings.mode, current, foreground, main_slot, &open);
+        if let Some(leader) = elected.filter(|&leader| Some(leader) != current) {
+            APP.leader.store(leader, Ordering::SeqCst);
+            println!("OMB {} is now the leader", leader);
+            if !APP.automation_paused.load(Ordering::SeqCst) {
+                if let Some(follow_key) = leader_settings.follow_key(leader) {
+                    tap_in_followers(leader, follow_key);
+                }
+            }
+        }
+        sleep(LEADER_POLL_INTERVAL);
+    }
+}
+
+// Put windows back into their slots when the game or the user moved or resized them
+fn run_pin_loop(pin_settings: &PinSettings) {
+    let mut states: HashMap<HwndWrapper, PinState> = HashMap::new();
+    while !is_shutting_down() {
+        let windows = omb_windows();
+        states.retain(|hwnd, _| windows.iter().any(|&(_, window)| window == hwnd.0));
+        // The user may be dragging a window, wait until they let go
+        let mouse_held = unsafe { GetAsyncKeyState(VK_LBUTTON.0 as i32) } < 0;
+
+        for (number, hwnd) in windows {
+            let state = states
+                .entry(HwndWrapper(hwnd))
+                .or_insert_with(PinState::new);
+            let Some(intended) =
+                get_slot_config(number).and_then(|config| placement_rect(config).ok())
+            else {
+                continue;
+            };
+            let mut actual = RECT::default();
+            unsafe {
+                if mouse_held
+                    || IsIconic(hwnd).as_bool()
+                    || GetWindowRect(hwnd, &mut actual).is_err()
+                {
+                    state.reset();
+                    continue;
+                }
+            }
+            let actual = Rect {
+                x: actual.left,
+                y: actual.top,
+                width: actual.right - actual.left,
+                height: actual.bottom - actual.top,
+            };
+            if state.observe(actual, intended, pin_settings) {
+                println!("[OMB {}] Left its slot, moving it back", number);
+                if let Some(config) = get_slot_config(number) {
+                    set_window_position(hwnd, config, get_slot_style(number));
+                }
+            }
+        }
+        sleep(pin_settings.check_interval());
+    }
+}
+
+// Tap a key in every registered window except the leader's
+fn tap_in_followers(leader: usize, vk: u16) {
+    let followers: Vec<HwndWrapper> = omb_windows()
+        .into_iter()
+        .filter(|&(number, _)| number != leader)
+        .map(|(_, hwnd)| HwndWrapper(hwnd))
+        .collect();
+    let trackers = APP.key_trackers.lock().unwrap();
+    for hwnd in followers {
+        if let Some(tracker) = trackers.get(&hwnd) {
+            tracker.tap(vk);
+        }
+    }
+}
+
 fn main() {
-    load_config();
-
-    // Initialize scancode mapping
-    let mut scancode_map: HashMap<u8, VIRTUAL_KEY> = HashMap::new();
-    scancode_map.insert(0x01, VK_F1);
-    scancode_map.insert(0x02, VK_F2);
-    scancode_map.insert(0x03, VK_F3);
-    scancode_map.insert(0x04, VK_F4);
-    scancode_map.insert(0x05, VK_F5);
-    scancode_map.insert(0x06, VK_F6);
-    scancode_map.insert(0x07, VK_F7);
-    scancode_map.insert(0x08, VK_F8);
-    scancode_map.insert(0x09, VK_F9);
-    scancode_map.insert(0x0A, VK_F10);
-    scancode_map.insert(0x0B, VK_F11);
-    scancode_map.insert(0x0C, VK_F12);
-    scancode_map.insert(0x0D, VK_F13);
-    scancode_map.insert(0x0E, VK_F14);
-    scancode_map.insert(0x0F, VK_F15);
-    scancode_map.insert(0x10, VK_F16);
-    scancode_map.insert(0x11, VK_F17);
-    scancode_map.insert(0x12, VK_F18);
-    scancode_map.insert(0x13, VK_F18);
-    scancode_map.insert(0x14, VK_F19);
-    scancode_map.insert(0x15, VK_F20);
-    scancode_map.insert(0x16, VK_F21);
-    scancode_map.insert(0x17, VK_F22);
-    scancode_map.insert(0x18, VK_F23);
-    scancode_map.insert(0x19, VK_F24);
-
-    let scancode_map_arc = Arc::new(Mutex::new(scancode_map));
-    let window_map: Arc<Mutex<HashMap<usize, HwndWrapper>>> = Arc::new(Mutex::new(HashMap::new()));
+    // Before anything looks at monitors or windows, which the layout commands do too
+    enable_dpi_awareness();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if control::run_cli_command(&args) {
+        return;
+    }
+
+    // Load the settings and configuration, reporting problems at startup rather than when
+    // they are first needed
+    LazyLock::force(&APP);
+    control::install_console_handler();
 
     // Start the watcher thread
-    std::thread::spawn(move || {
+    let watcher = std::thread::spawn(move || {
+        let mut window_threads: Vec<JoinHandle<()>> = Vec::new();
+        let mut first_pass = true;
         loop {
             sleep(Duration::from_millis(1000));
+            if is_shutting_down() {
+                break;
+            }
 
-            // Find all current windows with target title
-            let hwnds = find_all_windows_with_title();
+            // Windows of clients the tool started take the number they were started for
+            let starting = APP.launcher.lock().unwrap().running();
+            let discovered = discover(&Win32Windows, &APP.registry, launched_slot, &starting);
+            for Discovered {
+                window: wrapped,
+                title,
+                omb_number,
+            } in discovered
+            {
+                let hwnd = wrapped.0;
+                println!("Registered {} as OMB {}", title, omb_number);
+
+                let new_title = slot_title(omb_number);
+                if title != new_title {
+                    rename_window(hwnd, &new_title);
+                }
+                if let Some(config) = get_slot_config(omb_number) {
+                    set_window_position(hwnd, config, get_slot_style(omb_number));
+                }
 
-            for &hwnd in &hwnds {
-                let mut set = HWND_SET.lock().unwrap();
-                let wrapped = HwndWrapper(hwnd);
-                if !set.contains(&wrapped) {
-                    set.insert(wrapped);
+                // Held until the window closes, to tell a crash from quitting afterwards
+                let process = ProcessHandle::open(window_process_id(hwnd));
 
-                    // Spawn a new thread to handle this HWND
-                    let scancode_arc_clone = Arc::clone(&scancode_map_arc);
-                    let window_map_clone = Arc::clone(&window_map);
+                // Spawn a new thread to handle this HWND
+                window_threads.push(std::thread::spawn(move || {
+                    process_window(wrapped, omb_number, process)
+                }));
+            }
+            window_threads.retain(|thread| !thread.is_finished());
 
-                    std::thread::spawn(move || {
-                        process_window(wrapped, scancode_arc_clone, window_map_clone)
-                    });
+            // Titles only show the numbers, put back any that something else changed
+            for (omb_number, hwnd) in omb_windows() {
+                update_window_title(hwnd, omb_number);
+            }
+
+            // Start the missing clients once the windows already open have their numbers
+            if first_pass {
+                first_pass = false;
+                if APP
+                    .settings
+                    .launch
+                    .as_ref()
+                    .is_some_and(|launch| launch.at_startup)
+                {
+                    std::thread::spawn(launch_missing_clients);
                 }
             }
         }
+
+        // Window threads release their held keys on the way out
+        for thread in window_threads {
+            let _ = thread.join();
+        }
     });
 
-    // Keep main thread alive by setting up a message loop for the keyboard hook
+    let leader_election = APP
+        .settings
+        .leader
+        .as_ref()
+        .map(|leader_settings| std::thread::spawn(|| run_leader_election(leader_settings)));
+
+    let pin = APP
+        .settings
+        .pin
+        .as_ref()
+        .map(|pin_settings| std::thread::spawn(|| run_pin_loop(pin_settings)));
+
+    let crash_monitor = std::thread::spawn(run_crash_monitor);
+
+    // The keyboard hook and the control window need a message loop on the thread that
+    // created them. It gets its own thread so the dashboard can have the main thread.
+    let message_loop = std::thread::spawn(run_message_loop);
+    if APP.settings.dashboard {
+        // Closing the dashboard leaves the tool running
+        dashboard::run();
+    }
+    let _ = message_loop.join();
+
+    let _ = watcher.join();
+    if let Some(leader_election) = leader_election {
+        let _ = leader_election.join();
+    }
+    if let Some(pin) = pin {
+        let _ = pin.join();
+    }
+    let _ = crash_monitor.join();
+    release_all_keys();
+    restore_window_styles();
+    if APP.settings.restore_titles {
+        restore_window_titles();
+    }
+    APP.shutdown_complete.store(true, Ordering::SeqCst);
+    println!("Shutdown complete");
+}
+
+// Handle the keyboard hook and the control window's messages until shutdown
+fn run_message_loop() {
     unsafe {
         let hook = match SetWindowsHookExW(
             WH_KEYBOARD_LL,
@@ -357,33 +870,42 @@ fn main() {
             Ok(h) => h,
             Err(e) => {
                 println!("Failed to set keyboard hook: {}", e);
+                request_shutdown();
                 return;
             }
         };
 
-        let mut msg = MSG::default();
-        while GetMessageW(&mut msg, None, 0, 0).as_bool() {
-            let _ = TranslateMessage(&msg);
-            DispatchMessageW(&msg);
+        // Receives tray menu and CLI messages through this message loop
+        let control_window = control::create_control_window();
+
+        APP.message_loop_thread_id
+            .store(GetCurrentThreadId(), Ordering::SeqCst);
+        // A shutdown requested before the thread ID was known couldn't wake the loop
+        if !is_shutting_down() {
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
         }
 
         let _ = UnhookWindowsHookEx(hook);
+        if let Some(control_window) = control_window {
+            control::destroy_control_window(control_window);
+        }
     }
+    // The loop also ends if GetMessageW fails, make sure the workers stop either way
+    request_shutdown();
 }
 
-fn get_window_title_and_omb_number(hwnd: HWND) -> (String, Option<usize>) {
+fn get_window_title(hwnd: HWND) -> String {
     unsafe {
         let len = GetWindowTextLengthW(hwnd);
         let mut title_buf = vec![0u16; (len + 1) as usize];
         GetWindowTextW(hwnd, &mut title_buf);
-        let title = String::from_utf16_lossy(&title_buf)
+        String::from_utf16_lossy(&title_buf)
             .trim_end_matches('\0')
-            .to_string();
-        let number = title
-            .strip_prefix("OMB ")
-            .and_then(|s| s.split_whitespace().next())
-            .and_then(|s| s.parse().ok());
-        (title, number)
+            .to_string()
     }
 }
 
@@ -462,7 +984,11 @@ impl Drop for WindowCapturer {
     }
 }
 
-fn handle_key_toggle_command(blue: u8, _keys_enabled: &mut bool, title_string: &str) {
+fn handle_key_toggle_command(
+    command: ControlCommand,
+    _keys_enabled: &mut bool,
+    title_string: &str,
+) {
     // Old - left as reference to indicate these states are no longer used -- maybe use for something else in the future?
     // if blue == 1 && *keys_enabled {
     //     *keys_enabled = false;
@@ -472,13 +998,35 @@ fn handle_key_toggle_command(blue: u8, _keys_enabled: &mut bool, title_string: &
     //     println!("[{}] Keys enabled", title_string);
     // }
 
-    let mut broadcast_enabled = BROADCAST_ENABLED.lock().unwrap();
-    if blue == 3 && !*broadcast_enabled {
-        *broadcast_enabled = true;
-        println!("[{}] Broadcast enabled", title_string);
-    } else if blue == 4 && *broadcast_enabled {
-        *broadcast_enabled = false;
-        println!("[{}] Broadcast disabled", title_string);
+    match command {
+        ControlCommand::BroadcastOn => set_broadcast_enabled(true, title_string),
+        ControlCommand::BroadcastOff => set_broadcast_enabled(false, title_string),
+        _ => {}
+    }
+}
+
+// Turn broadcasting on or off and confirm the state to every addon
+fn set_broadcast_enabled(enabled: bool, source: &str) {
+    {
+        let mut broadcast_enabled = APP.broadcast_enabled.lock().unwrap();
+        if *broadcast_enabled != enabled {
+            *broadcast_enabled = enabled;
+            println!(
+                "[{}] Broadcast {}",
+                source,
+                if enabled { "enabled" } else { "disabled" }
+            );
+        }
+    }
+    send_feedback_to_all(Feedback::broadcast(enabled));
+}
+
+// Send feedback to the addon in every window
+fn send_feedback_to_all(feedback: Feedback) {
+    let trackers: Vec<Arc<KeyStateTracker>> =
+        APP.key_trackers.lock().unwrap().values().cloned().collect();
+    for tracker in trackers {
+        tracker.tap(feedback.key());
     }
 }
 
@@ -515,173 +1063,167 @@ fn swap_window_positions(hwnd1: HWND, hwnd2: HWND) {
     }
 }
 
-fn handle_window_swap(
+// Run a control command shown by the addon. Called once per command, when it first appears.
+// Returns the feedback that tells the addon whether it was carried out.
+fn handle_control_command(
     title_string: &str,
-    own_omb_num: Option<usize>,
-    wrapped: HwndWrapper,
-    blue: u8,
-    last_swap_time: &mut Instant,
-    window_map: &Arc<Mutex<HashMap<usize, HwndWrapper>>>,
-) {
-    if last_swap_time.elapsed() <= Duration::from_secs(1) {
-        return;
+    own_omb_num: usize,
+    command: ControlCommand,
+    keys_enabled: &mut bool,
+) -> Feedback {
+    let executed = match command {
+        ControlCommand::KeysDisabled
+        | ControlCommand::KeysEnabled
+        | ControlCommand::BroadcastOn
+        | ControlCommand::BroadcastOff => {
+            handle_key_toggle_command(command, keys_enabled, title_string);
+            true
+        }
+        ControlCommand::SwapWith(target_omb_num) => {
+            handle_window_swap(title_string, own_omb_num, target_omb_num)
+        }
+        ControlCommand::Focus {
+            omb_number,
+            into_main,
+        } => handle_focus_command(title_string, omb_number, into_main),
+        ControlCommand::ReleaseAll => {
+            release_all_keys();
+            true
+        }
+        ControlCommand::Unknown(blue) => {
+            println!("[{}] Unknown control command {}", title_string, blue);
+            false
+        }
+    };
+
+    if executed {
+        Feedback::CommandExecuted
+    } else {
+        Feedback::CommandFailed
     }
+}
 
-    let target_omb_num = (blue - 4) as usize;
+fn handle_window_swap(title_string: &str, own_omb_num: usize, target_omb_num: usize) -> bool {
     println!(
         "[{}] Received swap command with window {}",
         title_string, target_omb_num
     );
 
-    if let Some(own_num) = own_omb_num {
-        if own_num != target_omb_num {
-            let map = window_map.lock().unwrap();
-            if let Some(&target_hwnd_wrapper) = map.get(&target_omb_num) {
-                swap_window_positions(wrapped.0, target_hwnd_wrapper.0);
-            }
-        }
+    if own_omb_num != target_omb_num && find_window_by_omb_number(target_omb_num).is_some() {
+        swap_slots(own_omb_num, target_omb_num);
+        true
+    } else {
+        false
     }
-    *last_swap_time = Instant::now();
 }
 
-fn send_keypress(hwnd: HWND, vk: VIRTUAL_KEY) {
-    unsafe {
-        let _ = PostMessageW(
-            Some(hwnd),
-            WM_KEYDOWN as u32,
-            WPARAM(vk.0.into()),
-            LPARAM(0),
-        );
-        // sleep(Duration::from_millis(10));
-        let _ = PostMessageW(Some(hwnd), WM_KEYUP as u32, WPARAM(vk.0.into()), LPARAM(0));
+// Focus the window named by a focus control command
+fn handle_focus_command(title_string: &str, target_omb_num: usize, into_main: bool) -> bool {
+    println!(
+        "[{}] Received focus command for window {}",
+        title_string, target_omb_num
+    );
+    focus_slot(target_omb_num, into_main)
+}
+
+// Release every key held in every window
+fn release_all_keys() {
+    let trackers: Vec<Arc<KeyStateTracker>> =
+        APP.key_trackers.lock().unwrap().values().cloned().collect();
+    for tracker in trackers {
+        tracker.release_all();
     }
+    println!("Released all held keys");
 }
 
-fn handle_key_press(
-    hwnd: HWND,
-    red: u8,
-    green: u8,
-    scancode_map_arc: &Arc<Mutex<HashMap<u8, VIRTUAL_KEY>>>,
-) -> bool {
-    let scancode_map = scancode_map_arc.lock().unwrap();
-    if let Some(&scancode) = scancode_map.get(&red) {
-        send_target_combination(hwnd, green);
-        send_keypress(hwnd, scancode);
-        // sleep(Duration::from_millis(100));
-        return true;
+// Log a window's state change and publish it
+fn report_window_state(wrapped: HwndWrapper, title_string: &str, transition: Option<Transition>) {
+    let Some(transition) = transition else {
+        return;
+    };
+    let mut entries = APP.window_status.lock().unwrap();
+    let Some(entry) = entries.get_mut(&wrapped) else {
+        return;
+    };
+    println!(
+        "[{}] {:?} -> {:?} after {:.1}s",
+        title_string,
+        transition.from,
+        transition.to,
+        transition.after.as_secs_f32()
+    );
+    entry.status.apply(&transition);
+
+    let omb_number = entry.omb_number;
+
+    let mut all: Vec<WindowStatus> = entries.values().map(|entry| entry.status.clone()).collect();
+    all.sort_by(|a, b| a.title.cmp(&b.title));
+    save_window_status(&all);
+    drop(entries);
+
+    update_window_title(wrapped.0, omb_number);
+}
+
+// Publish what the last frame of a window led to
+fn update_window_entry(wrapped: HwndWrapper, capture_latency: Duration, outcome: FrameOutcome) {
+    let mut entries = APP.window_status.lock().unwrap();
+    let Some(entry) = entries.get_mut(&wrapped) else {
+        return;
+    };
+    entry.capture_latency = capture_latency;
+    if outcome.macro_pressed {
+        entry.record_macro_press(Instant::now());
+    }
+    if let Some((command, feedback)) = outcome.control_command {
+        entry.last_command = Some(format!("{:?} ({:?})", command, feedback));
     }
-    false
 }
 
-fn handle_movement_rotation_command(hwnd: HWND, bitmask: u8, last_bitmask: u8) {
-    unsafe {
-        // Bit 0: Rotate Right
-        if (bitmask & 1) != last_bitmask & 1 {
-            if(bitmask & 1) == 0 {
-                //finished rotating left
-                PostMessageW(Some(hwnd), WM_KEYUP as u32, WPARAM(VK_RIGHT.0.into()), LPARAM(0));
-            } else {
-                //start rotating left
-                let _ = PostMessageW(
-                    Some(hwnd),
-                    WM_KEYDOWN as u32,
-                    WPARAM(VK_RIGHT.0.into()),
-                    LPARAM(0),
-                );
-            }
-        }
-        // Bit 1: Rotate Left
-        if (bitmask & 2) != last_bitmask & 2 {
-            if(bitmask & 2) == 0 {
-                //finished rotating left
-                PostMessageW(Some(hwnd), WM_KEYUP as u32, WPARAM(VK_LEFT.0.into()), LPARAM(0));
-            } else {
-                //start rotating left
-                let _ = PostMessageW(
-                    Some(hwnd),
-                    WM_KEYDOWN as u32,
-                    WPARAM(VK_LEFT.0.into()),
-                    LPARAM(0),
-                );
-            }
-        }
-        // Bit 2: Move Forward
-        if (bitmask & 4) != last_bitmask & 4 {
-            if(bitmask & 4) == 0 {
-                //finished moving forward
-                PostMessageW(Some(hwnd), WM_KEYUP as u32, WPARAM(VK_UP.0.into()), LPARAM(0));
-            } else {
-                //start moving forward
-                let _ = PostMessageW(
-                    Some(hwnd),
-                    WM_KEYDOWN as u32,
-                    WPARAM(VK_UP.0.into()),
-                    LPARAM(0),
-                );
-            }
-        }
-        // Bit 3: Move Backward
-        if (bitmask & 8) != last_bitmask & 8 {
-            if(bitmask & 8) == 0 {
-                //finished moving backward
-                PostMessageW(Some(hwnd), WM_KEYUP as u32, WPARAM(VK_DOWN.0.into()), LPARAM(0));
-            } else {
-                //start moving backward
-                let _ = PostMessageW(
-                    Some(hwnd),
-                    WM_KEYDOWN as u32,
-                    WPARAM(VK_DOWN.0.into()),
-                    LPARAM(0),
-                );
-            }
-        }
-        // Bit 4: Move Right (strafe)
-        if (bitmask & 16) != last_bitmask & 16 {
-            if(bitmask & 16) == 0 {
-                //finished moving right
-                PostMessageW(Some(hwnd), WM_KEYUP as u32, WPARAM(VK_D.0.into()), LPARAM(0));
-            } else {
-                //start moving right
-                let _ = PostMessageW(
-                    Some(hwnd),
-                    WM_KEYDOWN as u32,
-                    WPARAM(VK_D.0.into()),
-                    LPARAM(0),
-                );
-            }
-        }
-        // Bit 5: Move Left (strafe)
-        if (bitmask & 32) != last_bitmask & 32 {
-            if(bitmask & 32) == 0 {
-                //finished moving left
-                PostMessageW(Some(hwnd), WM_KEYUP as u32, WPARAM(VK_A.0.into()), LPARAM(0));
-            } else {
-                //start moving left
-                let _ = PostMessageW(
-                    Some(hwnd),
-                    WM_KEYDOWN as u32,
-                    WPARAM(VK_A.0.into()),
-                    LPARAM(0),
-                );
-            }
+// Carries out control commands shown by the addon in one window
+struct WindowControl<'a> {
+    title_string: &'a str,
+    own_omb_num: usize,
+    recording: Option<WindowRecording>,
+}
+
+impl ControlHandler for WindowControl<'_> {
+    fn handle(&self, command: ControlCommand, keys_enabled: &mut bool) -> Feedback {
+        let feedback =
+            handle_control_command(self.title_string, self.own_omb_num, command, keys_enabled);
+        if let Some(recording) = self.recording {
+            recording.record(RecordedEvent::Command { feedback });
         }
+        feedback
     }
 }
 
-// Process a single HWND
-fn process_window(
-    wrapped: HwndWrapper,
-    scancode_map_arc: Arc<Mutex<HashMap<u8, VIRTUAL_KEY>>>,
-    window_map: Arc<Mutex<HashMap<usize, HwndWrapper>>>,
-) {
+// Process a single HWND, registered under an OMB number by the watcher
+fn process_window(wrapped: HwndWrapper, own_omb_num: usize, process: Option<ProcessHandle>) {
     let hwnd = wrapped.0;
-    let mut keys_enabled = true;
-    let mut last_swap_time = Instant::now();
 
-    let (title_string, own_omb_num) = get_window_title_and_omb_number(hwnd);
+    let title_string = get_window_title(hwnd);
+    let mut health = WindowHealth::new();
+    let character = get_character(own_omb_num - 1);
+    APP.window_status.lock().unwrap().insert(
+        wrapped,
+        WindowEntry::new(&title_string, own_omb_num, character),
+    );
 
-    if let Some(num) = own_omb_num {
-        window_map.lock().unwrap().insert(num, wrapped);
+    let injection_method = get_injection_method(own_omb_num - 1);
+    let recording = APP
+        .recorder
+        .as_ref()
+        .map(|recorder| recorder.start_window(&title_string, injection_method));
+    let injector = Arc::new(KeyStateTracker::new(KeyRecorder::wrap(
+        create_injector(injection_method, wrapped, &title_string),
+        recording,
+    )));
+    APP.key_trackers
+        .lock()
+        .unwrap()
+        .insert(wrapped, Arc::clone(&injector));
+    if let Some(recording) = recording {
+        APP.recordings.lock().unwrap().insert(wrapped, recording);
     }
 
     // Initialize the GDI capturer once.
@@ -690,110 +1232,97 @@ fn process_window(
 
     if capturer.is_none() {
         println!("[{}] Failed to initialize GDI capturer.", title_string);
+        report_window_state(
+            wrapped,
+            &title_string,
+            health.observe(Observation::CaptureFailed),
+        );
     } else {
         let capturer = capturer.as_ref().unwrap();
-        let mut loops_since_last_keypress = 0;
-        let mut last_movement_bitmask = 0;
+        let mut pipeline = WindowPipeline::new(
+            &title_string,
+            injection_method == InjectionMethod::SendInput,
+        );
+        let control = WindowControl {
+            title_string: &title_string,
+            own_omb_num,
+            recording,
+        };
         loop {
             unsafe {
-                if !IsWindow(Some(hwnd)).as_bool() {
+                if !IsWindow(Some(hwnd)).as_bool() || is_shutting_down() {
                     break;
                 }
             }
 
             // Reuse the existing GDI context
-            if let Some((sentinel, actual_color, movement_rotation_color)) = capturer.capture() {
-                // Check sentinel color to ensure addon is active
-                if sentinel == SENTINEL_COLOR {
-                    let blue = ((actual_color >> 16) & 0xFF) as u8;
-                    let green = ((actual_color >> 8) & 0xFF) as u8;
-                    let red = (actual_color & 0xFF) as u8;
-                    let movement_rotation_bitmask = (movement_rotation_color & 0xFF) as u8; // Red component only
-
-                    handle_key_toggle_command(blue, &mut keys_enabled, &title_string);
-
-                    // 0 = do nothing, 1/2 = unused, 3/4 = broadcast, >4 = swap
-                    if blue > 4 {
-                        handle_window_swap(
-                            &title_string,
-                            own_omb_num,
-                            wrapped,
-                            blue,
-                            &mut last_swap_time,
-                            &window_map,
-                        );
-                    }
-
-                    if movement_rotation_bitmask != last_movement_bitmask {
-                        handle_movement_rotation_command(
-                            hwnd,
-                            movement_rotation_bitmask,
-                            last_movement_bitmask,
-                        );
-                        last_movement_bitmask = movement_rotation_bitmask;
-                        // Consider adding a small sleep here if movement commands are sent too rapidly
-                        // sleep(Duration::from_millis(10));
-                    }
-
-                    if keys_enabled && loops_since_last_keypress >= 30 {
-                        let has_pressed_key = handle_key_press(hwnd, red, green, &scancode_map_arc);
-                        if has_pressed_key {
-                            // Reset loop counter on keypress
-                            loops_since_last_keypress = 0;
-                        }
-                    }
-                }
+            let capture_started = Instant::now();
+            let capture = capturer.capture();
+            let capture_latency = capture_started.elapsed();
+            let observation = match capture {
+                None => Observation::CaptureFailed,
+                Some((sentinel, _, _)) if sentinel != SENTINEL_COLOR => Observation::AddonMissing,
+                Some((_, _, movement_rotation_color)) => Observation::Addon {
+                    heartbeat: MovementPixel::decode(movement_rotation_color).heartbeat,
+                },
+            };
+            report_window_state(wrapped, &title_string, health.observe(observation));
+
+            let frame = Frame {
+                // Check the sentinel color to ensure the addon is active
+                pixels: capture
+                    .filter(|&(sentinel, _, _)| sentinel == SENTINEL_COLOR)
+                    .map(|(_, actual_color, movement_rotation_color)| {
+                        (actual_color, movement_rotation_color)
+                    }),
+                broadcast_enabled: *APP.broadcast_enabled.lock().unwrap(),
+                paused: APP.automation_paused.load(Ordering::SeqCst),
+                foreground: unsafe { GetForegroundWindow() } == hwnd,
+            };
+            if let Some(recording) = recording {
+                recording.record(RecordedEvent::Frame(frame));
             }
-            loops_since_last_keypress += 1;
+            let outcome = pipeline.process(&frame, &injector, &control);
+            update_window_entry(wrapped, capture_latency, outcome);
+
             // Sleep between checks
             sleep(Duration::from_millis(3));
         }
     }
     // GDI resources in 'capturer' are automatically cleaned up here via Drop
 
-    if let Some(num) = own_omb_num {
-        window_map.lock().unwrap().remove(&num);
-        println!("[{}] Unregistered window.", title_string);
-    }
-}
-
-fn send_target_combination(hwnd: HWND, input: u8) {
-    if input == 0 {
-        return;
-    }
-    let mod_index = (input - 1) % 4;
-    let numpad_index = (input - 1) / 4;
-
-    let modifier_vk: Option<VIRTUAL_KEY> = match mod_index {
-        1 => Some(VK_LCONTROL),
-        2 => Some(VK_LSHIFT),
-        3 => Some(VK_LMENU),
-        _ => None,
-    };
-
-    let numpad_key: usize = <u16 as Into<usize>>::into(VK_NUMPAD0.0) + (numpad_index as usize);
-    unsafe {
-        if let Some(modifier) = modifier_vk {
-            let _ = PostMessageW(
-                Some(hwnd),
-                WM_KEYDOWN as u32,
-                WPARAM(modifier.0.into()),
-                LPARAM(0),
-            );
+    if unsafe { !IsWindow(Some(hwnd)).as_bool() } {
+        let transition = health.close();
+        report_window_state(wrapped, &title_string, transition);
+        // The window closed with the character in the world, as the addon still drew its
+        // pixels. Quitting from there ends the client cleanly, a crash doesn't.
+        if transition.is_some_and(|transition| {
+            matches!(transition.from, WindowState::Active | WindowState::Stale)
+        }) {
+            let exit = process
+                .as_ref()
+                .map(|process| (process.id(), process.wait_for_exit(CLIENT_EXIT_TIMEOUT)));
+            match exit {
+                Some((_, ProcessExit::Exited(0))) => {}
+                Some((process_id, ProcessExit::Exited(code))) => {
+                    report_crash(own_omb_num, process_id, CrashCause::ExitCode(code as i32))
+                }
+                Some((process_id, ProcessExit::Running)) => {
+                    report_crash(own_omb_num, process_id, CrashCause::WindowVanished)
+                }
+                // Without the process there is nothing to tell a crash from quitting by
+                Some((_, ProcessExit::Unknown)) | None => println!(
+                    "[{}] Can't tell whether the client crashed or quit",
+                    title_string
+                ),
+            }
         }
+    }
 
-        let _ = PostMessageW(Some(hwnd), WM_KEYDOWN as u32, WPARAM(numpad_key), LPARAM(0));
-        // std::thread::sleep(std::time::Duration::from_millis(10));
-        let _ = PostMessageW(Some(hwnd), WM_KEYUP as u32, WPARAM(numpad_key), LPARAM(0));
+    injector.release_all();
+    APP.key_trackers.lock().unwrap().remove(&wrapped);
+    APP.recordings.lock().unwrap().remove(&wrapped);
 
-        if let Some(modifier) = modifier_vk {
-            let _ = PostMessageW(
-                Some(hwnd),
-                WM_KEYUP as u32,
-                WPARAM(modifier.0.into()),
-                LPARAM(0),
-            );
-        }
-        // std::thread::sleep(std::time::Duration::from_millis(10));
-    }
+    APP.registry.lock().unwrap().unregister(wrapped);
+    println!("[{}] Unregistered window.", title_string);
 }
@@ -0,0 +1,48 @@
+use crate::layout::{MonitorRef, Rect};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    // Device name, e.g. "\\.\DISPLAY1"
+    pub name: String,
+    // Position on the desktop, in physical pixels
+    pub bounds: Rect,
+    pub primary: bool,
+    // Windows' display scaling, 1.0 at 100%
+    pub scale: f32,
+}
+
+// Where the monitors come from, so layouts can be resolved against made up ones
+pub trait MonitorSource {
+    // All monitors, the primary one first and the others from left to right
+    fn monitors(&self) -> Vec<Monitor>;
+}
+
+// The same monitors every time
+#[cfg(test)]
+pub struct FixedMonitors(pub Vec<Monitor>);
+
+#[cfg(test)]
+impl MonitorSource for FixedMonitors {
+    fn monitors(&self) -> Vec<Monitor> {
+        self.0.clone()
+    }
+}
+
+// Put the primary monitor first and the others from left to right, the order monitor
+// numbers count in
+pub fn sort_monitors(monitors: &mut [Monitor]) {
+    monitors.sort_by_key(|monitor| (!monitor.primary, monitor.bounds.x, monitor.bounds.y));
+}
+
+pub fn find_monitor<'a>(monitors: &'a [Monitor], reference: &MonitorRef) -> Option<&'a Monitor> {
+    match reference {
+        MonitorRef::Number(number) => number.checked_sub(1).and_then(|i| monitors.get(i)),
+        // "DISPLAY2" matches "\\.\DISPLAY2"
+        MonitorRef::Name(name) => monitors.iter().find(|monitor| {
+            monitor
+                .name
+                .trim_start_matches("\\\\.\\")
+                .eq_ignore_ascii_case(name.trim_start_matches("\\\\.\\"))
+        }),
+    }
+}
@@ -0,0 +1,498 @@
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    injector::KeyInjector,
+    protocol::{MovementPixel, MAX_DISTANCE},
+};
+
+// Bits of the movement/rotation bitmask in the red channel of the movement pixel
+pub const ROTATE_RIGHT: u8 = 1;
+pub const ROTATE_LEFT: u8 = 2;
+pub const MOVE_FORWARD: u8 = 4;
+pub const MOVE_BACKWARD: u8 = 8;
+pub const STRAFE_RIGHT: u8 = 16;
+pub const STRAFE_LEFT: u8 = 32;
+
+// Keys held for each bit of the movement/rotation bitmask
+pub const MOVEMENT_KEYS: [(u8, u16); 6] = [
+    (ROTATE_RIGHT, 0x27),  // VK_RIGHT
+    (ROTATE_LEFT, 0x25),   // VK_LEFT
+    (MOVE_FORWARD, 0x26),  // VK_UP
+    (MOVE_BACKWARD, 0x28), // VK_DOWN
+    (STRAFE_RIGHT, 0x44),  // D
+    (STRAFE_LEFT, 0x41),   // A
+];
+
+// Opposite directions that cancel out when both are requested
+const OPPOSITES: [(u8, u8); 3] = [
+    (ROTATE_RIGHT, ROTATE_LEFT),
+    (MOVE_FORWARD, MOVE_BACKWARD),
+    (STRAFE_RIGHT, STRAFE_LEFT),
+];
+
+// A key held this long is released, and stays up until its bit clears.
+// Protects against a frozen game or addon leaving the character running forever.
+pub const MAX_HOLD_DURATION: Duration = Duration::from_secs(5);
+
+// Rotation starts with short pulses so small corrections don't overshoot,
+// and is held once the addon has asked for it this long
+pub const ROTATION_HOLD_AFTER: Duration = Duration::from_millis(250);
+pub const ROTATION_PULSE_ON: Duration = Duration::from_millis(40);
+pub const ROTATION_PULSE_OFF: Duration = Duration::from_millis(40);
+
+// Kinematic model of a character the analog controller steers by.
+// Key holds are sized from how far the character turns or runs while a key is down.
+pub const TURN_RATE: f32 = std::f32::consts::PI; // Radians per second
+pub const RUN_SPEED: f32 = 7.0; // Yards per second
+pub const BACKPEDAL_SPEED: f32 = 4.5; // Yards per second
+
+// The analog controller decides once per period how long to hold each key within it.
+// Holds shorter than the minimum are rounded up, a single frame may not register them.
+pub const CONTROL_PERIOD: Duration = Duration::from_millis(100);
+pub const MIN_PULSE: Duration = Duration::from_millis(20);
+
+// Errors the analog controller stops correcting at.
+// Multibox.lua clears its target within the same tolerances.
+pub const HEADING_TOLERANCE: f32 = 0.035; // Radians
+pub const DISTANCE_TOLERANCE: f32 = 0.25; // Yards
+
+// The controller stops pulsing when the heartbeat bit hasn't flipped this long, the game or
+// addon froze, or when analog values within the pixel's range haven't changed this long, the
+// character is stuck. A target beyond MAX_DISTANCE reads the same every frame until the
+// character gets closer, so that alone doesn't count as stuck.
+pub const STALE_SAMPLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Source of the current time, so the controller can be driven by a fake clock
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// Clock moved forward by hand, shared by the simulator or replay and the controller it drives.
+// Outside tests only the replay binary uses it.
+#[derive(Clone)]
+#[cfg_attr(not(test), allow(dead_code))]
+pub struct SimulatedClock {
+    now: Rc<Cell<Instant>>,
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+impl SimulatedClock {
+    pub fn new() -> Self {
+        Self {
+            now: Rc::new(Cell::new(Instant::now())),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+}
+
+// Drop both bits of every pair of opposite directions that are requested together
+pub fn resolve_contradictions(bitmask: u8) -> u8 {
+    OPPOSITES.iter().fold(bitmask, |bitmask, &(a, b)| {
+        if bitmask & (a | b) == a | b {
+            bitmask & !(a | b)
+        } else {
+            bitmask
+        }
+    })
+}
+
+// How long to hold a key to correct an error at the given rate, within one control period
+pub fn pulse_width(error: f32, rate: f32, tolerance: f32) -> Duration {
+    if error.abs() <= tolerance {
+        return Duration::ZERO;
+    }
+    Duration::from_secs_f32(error.abs() / rate).clamp(MIN_PULSE, CONTROL_PERIOD)
+}
+
+// Heading error and distance from the movement pixel
+type AnalogSample = (Option<f32>, Option<f32>);
+
+// The analog key holds planned for the current control period
+struct AnalogPulse {
+    started: Instant,
+    // Rotation or forward/backward bit and how long to hold it, if any
+    heading: Option<(u8, Duration)>,
+    distance: Option<(u8, Duration)>,
+}
+
+impl AnalogPulse {
+    fn plan(started: Instant, heading_error: Option<f32>, distance: Option<f32>) -> Self {
+        let heading = heading_error.map(|error| {
+            let bit = if error > 0.0 {
+                ROTATE_LEFT
+            } else {
+                ROTATE_RIGHT
+            };
+            (bit, pulse_width(error, TURN_RATE, HEADING_TOLERANCE))
+        });
+        let distance = distance.map(|distance| {
+            if distance > 0.0 {
+                let hold = pulse_width(distance, RUN_SPEED, DISTANCE_TOLERANCE);
+                (MOVE_FORWARD, hold)
+            } else {
+                let hold = pulse_width(distance, BACKPEDAL_SPEED, DISTANCE_TOLERANCE);
+                (MOVE_BACKWARD, hold)
+            }
+        });
+        Self {
+            started,
+            heading,
+            distance,
+        }
+    }
+
+    // Bits that should be down at the given time
+    fn bits(&self, now: Instant) -> u8 {
+        let elapsed = now.duration_since(self.started);
+        [self.heading, self.distance]
+            .into_iter()
+            .flatten()
+            .filter(|&(_, hold)| elapsed < hold)
+            .fold(0, |bits, (bit, _)| bits | bit)
+    }
+}
+
+// Turns the movement bitmask the addon shows into key presses.
+// Must be updated every frame, not only when the bitmask changes, so timeouts and pulses work.
+pub struct MovementController<C: Clock = SystemClock> {
+    clock: C,
+    // Keys currently held down and since when
+    held: BTreeMap<u16, Instant>,
+    // Keys released for being held too long, kept up until their bit clears
+    expired: BTreeSet<u16>,
+    // Rotation bits currently requested and since when
+    rotation: Option<(u8, Instant)>,
+    // Last heartbeat bit seen and since when it hasn't flipped
+    heartbeat: Option<(bool, Instant)>,
+    // Last analog values seen and since when they haven't changed
+    sample: Option<(AnalogSample, Instant)>,
+    analog: Option<AnalogPulse>,
+}
+
+impl<C: Clock> MovementController<C> {
+    pub fn with_clock(clock: C) -> Self {
+        Self {
+            clock,
+            held: BTreeMap::new(),
+            expired: BTreeSet::new(),
+            rotation: None,
+            heartbeat: None,
+            sample: None,
+            analog: None,
+        }
+    }
+
+    pub fn update(&mut self, injector: &dyn KeyInjector, pixel: MovementPixel) {
+        let now = self.clock.now();
+        let mut bitmask = resolve_contradictions(pixel.bitmask);
+
+        // Analog values take over from the bits they describe
+        if pixel.heading_error.is_some() {
+            bitmask &= !(ROTATE_RIGHT | ROTATE_LEFT);
+        }
+        if pixel.distance.is_some() {
+            bitmask &= !(MOVE_FORWARD | MOVE_BACKWARD);
+        }
+        let flipped_at = match self.heartbeat {
+            Some((last, since)) if last == pixel.heartbeat => since,
+            _ => now,
+        };
+        self.heartbeat = Some((pixel.heartbeat, flipped_at));
+        let analog = self.update_analog(now, pixel.heading_error, pixel.distance);
+
+        let rotation_bits = bitmask & (ROTATE_RIGHT | ROTATE_LEFT);
+        self.rotation = match self.rotation {
+            _ if rotation_bits == 0 => None,
+            Some((bits, since)) if bits == rotation_bits => Some((bits, since)),
+            _ => Some((rotation_bits, now)),
+        };
+
+        for (bit, vk) in MOVEMENT_KEYS {
+            let is_analog = analog & bit != 0;
+            let requested = bitmask & bit != 0;
+            if !requested {
+                self.expired.remove(&vk);
+            }
+            let is_rotation = bit & (ROTATE_RIGHT | ROTATE_LEFT) != 0;
+            let want_down = is_analog
+                || requested
+                    && !self.expired.contains(&vk)
+                    && (!is_rotation || self.rotation_on(now));
+
+            match (want_down, self.held.get(&vk)) {
+                (true, None) => {
+                    injector.key_down(vk);
+                    self.held.insert(vk, now);
+                }
+                // Analog holds are bounded by STALE_SAMPLE_TIMEOUT instead
+                (true, Some(&since))
+                    if !is_analog && now.duration_since(since) >= MAX_HOLD_DURATION =>
+                {
+                    println!("Movement key {} held too long, releasing it", vk);
+                    injector.key_up(vk);
+                    self.held.remove(&vk);
+                    self.expired.insert(vk);
+                }
+                (false, Some(_)) => {
+                    injector.key_up(vk);
+                    self.held.remove(&vk);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Bits the analog controller wants down right now
+    fn update_analog(&mut self, now: Instant, heading: Option<f32>, distance: Option<f32>) -> u8 {
+        if heading.is_none() && distance.is_none() {
+            self.sample = None;
+            self.analog = None;
+            return 0;
+        }
+
+        let values = (heading, distance);
+        let unchanged_since = match self.sample {
+            Some((last, since)) if last == values => since,
+            _ => now,
+        };
+        self.sample = Some((values, unchanged_since));
+        let frozen = self
+            .heartbeat
+            .is_some_and(|(_, since)| now.duration_since(since) >= STALE_SAMPLE_TIMEOUT);
+        let out_of_range = distance.is_some_and(|distance| distance.abs() >= MAX_DISTANCE);
+        let stuck = !out_of_range && now.duration_since(unchanged_since) >= STALE_SAMPLE_TIMEOUT;
+        if frozen || stuck {
+            self.analog = None;
+            return 0;
+        }
+
+        // Plan a new period from the latest values once the current one is over
+        let period_over = self
+            .analog
+            .as_ref()
+            .is_none_or(|pulse| now.duration_since(pulse.started) >= CONTROL_PERIOD);
+        if period_over {
+            self.analog = Some(AnalogPulse::plan(now, heading, distance));
+        }
+        self.analog.as_ref().map_or(0, |pulse| pulse.bits(now))
+    }
+
+    // Whether rotation keys should be down right now: pulsed at first, then held
+    fn rotation_on(&self, now: Instant) -> bool {
+        let Some((_, since)) = self.rotation else {
+            return false;
+        };
+        let elapsed = now.duration_since(since);
+        if elapsed >= ROTATION_HOLD_AFTER {
+            return true;
+        }
+        let period = (ROTATION_PULSE_ON + ROTATION_PULSE_OFF).as_millis();
+        elapsed.as_millis() % period < ROTATION_PULSE_ON.as_millis()
+    }
+
+    // Release every held movement key and forget all timers
+    pub fn release(&mut self, injector: &dyn KeyInjector) {
+        for vk in std::mem::take(&mut self.held).into_keys() {
+            injector.key_up(vk);
+        }
+        self.expired.clear();
+        self.rotation = None;
+        self.heartbeat = None;
+        self.sample = None;
+        self.analog = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{injector::RecordingInjector, keystate::KeyStateTracker};
+
+    const VK_RIGHT: u16 = 0x27;
+    const VK_LEFT: u16 = 0x25;
+    const VK_UP: u16 = 0x26;
+
+    struct Harness {
+        clock: SimulatedClock,
+        keys: KeyStateTracker,
+        controller: MovementController<SimulatedClock>,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            let clock = SimulatedClock::new();
+            Self {
+                controller: MovementController::with_clock(clock.clone()),
+                clock,
+                keys: KeyStateTracker::new(Box::new(RecordingInjector::silent())),
+            }
+        }
+
+        // Show the pixel for a frame, then let time pass. Returns the keys held during it.
+        fn show(&mut self, pixel: MovementPixel, duration: Duration) -> Vec<u16> {
+            self.controller.update(&self.keys, pixel);
+            self.clock.advance(duration);
+            self.keys.held_keys()
+        }
+
+        fn frame(&mut self, bitmask: u8, duration: Duration) -> Vec<u16> {
+            let pixel = MovementPixel {
+                bitmask,
+                ..Default::default()
+            };
+            self.show(pixel, duration)
+        }
+
+        // Show the same distance for a while, with the heartbeat flipping or not.
+        // Returns whether forward was held in every frame.
+        fn hold_distance(&mut self, distance: f32, duration: Duration, alive: bool) -> bool {
+            let mut always_forward = true;
+            let mut heartbeat = false;
+            let mut elapsed = Duration::ZERO;
+            while elapsed < duration {
+                if alive && elapsed.as_millis().is_multiple_of(250) {
+                    heartbeat = !heartbeat;
+                }
+                let pixel = MovementPixel {
+                    bitmask: MOVE_FORWARD,
+                    heartbeat,
+                    heading_error: None,
+                    distance: Some(distance),
+                };
+                always_forward &= self.show(pixel, FRAME) == vec![VK_UP];
+                elapsed += FRAME;
+            }
+            always_forward
+        }
+    }
+
+    const FRAME: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn opposite_directions_cancel_out() {
+        assert_eq!(resolve_contradictions(MOVE_FORWARD | MOVE_BACKWARD), 0);
+        assert_eq!(resolve_contradictions(ROTATE_LEFT | ROTATE_RIGHT), 0);
+        assert_eq!(
+            resolve_contradictions(STRAFE_LEFT | STRAFE_RIGHT | MOVE_FORWARD),
+            MOVE_FORWARD
+        );
+        assert_eq!(
+            resolve_contradictions(MOVE_FORWARD | STRAFE_LEFT),
+            MOVE_FORWARD | STRAFE_LEFT
+        );
+
+        let mut harness = Harness::new();
+        assert!(harness
+            .frame(MOVE_FORWARD | MOVE_BACKWARD, FRAME)
+            .is_empty());
+    }
+
+    #[test]
+    fn key_held_too_long_is_released_until_its_bit_clears() {
+        let mut harness = Harness::new();
+        assert_eq!(harness.frame(MOVE_FORWARD, MAX_HOLD_DURATION), vec![VK_UP]);
+        // Released at the first frame after MAX_HOLD_DURATION, and kept up
+        assert!(harness.frame(MOVE_FORWARD, FRAME).is_empty());
+        assert!(harness.frame(MOVE_FORWARD, MAX_HOLD_DURATION).is_empty());
+
+        // Pressed again once the addon stops asking and asks again
+        assert!(harness.frame(0, FRAME).is_empty());
+        assert_eq!(harness.frame(MOVE_FORWARD, FRAME), vec![VK_UP]);
+    }
+
+    #[test]
+    fn key_held_just_under_the_limit_stays_down() {
+        let mut harness = Harness::new();
+        harness.frame(MOVE_FORWARD, MAX_HOLD_DURATION - FRAME);
+        assert_eq!(harness.frame(MOVE_FORWARD, FRAME), vec![VK_UP]);
+    }
+
+    #[test]
+    fn rotation_is_pulsed_then_held() {
+        let mut harness = Harness::new();
+        let mut elapsed = Duration::ZERO;
+        while elapsed < ROTATION_HOLD_AFTER {
+            let held = harness.frame(ROTATE_LEFT, FRAME);
+            let period = (ROTATION_PULSE_ON + ROTATION_PULSE_OFF).as_millis();
+            let pulse_on = elapsed.as_millis() % period < ROTATION_PULSE_ON.as_millis();
+            assert_eq!(!held.is_empty(), pulse_on, "after {:?}", elapsed);
+            elapsed += FRAME;
+        }
+        for _ in 0..50 {
+            assert_eq!(harness.frame(ROTATE_LEFT, FRAME), vec![VK_LEFT]);
+        }
+    }
+
+    #[test]
+    fn changing_rotation_direction_starts_pulsing_again() {
+        let mut harness = Harness::new();
+        harness.frame(ROTATE_LEFT, ROTATION_HOLD_AFTER);
+        assert_eq!(harness.frame(ROTATE_LEFT, FRAME), vec![VK_LEFT]);
+        assert_eq!(
+            harness.frame(ROTATE_RIGHT, ROTATION_PULSE_ON),
+            vec![VK_RIGHT]
+        );
+        assert!(harness.frame(ROTATE_RIGHT, FRAME).is_empty());
+    }
+
+    #[test]
+    fn target_beyond_the_pixels_range_is_run_to() {
+        let mut harness = Harness::new();
+        assert!(harness.hold_distance(MAX_DISTANCE, 4 * STALE_SAMPLE_TIMEOUT, true));
+    }
+
+    #[test]
+    fn frozen_heartbeat_stops_the_analog_controller() {
+        let mut harness = Harness::new();
+        assert!(harness.hold_distance(MAX_DISTANCE, STALE_SAMPLE_TIMEOUT / 2, true));
+        harness.hold_distance(MAX_DISTANCE, STALE_SAMPLE_TIMEOUT, false);
+        assert!(harness
+            .show(
+                MovementPixel {
+                    distance: Some(MAX_DISTANCE),
+                    ..Default::default()
+                },
+                FRAME
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn unchanging_distance_in_range_means_stuck() {
+        let mut harness = Harness::new();
+        assert!(harness.hold_distance(20.0, STALE_SAMPLE_TIMEOUT / 2, true));
+        assert!(!harness.hold_distance(20.0, STALE_SAMPLE_TIMEOUT, true));
+        assert!(harness.keys.held_keys().is_empty());
+    }
+
+    #[test]
+    fn release_lets_go_of_every_key() {
+        let mut harness = Harness::new();
+        harness.frame(MOVE_FORWARD | STRAFE_LEFT, FRAME);
+        harness.controller.release(&harness.keys);
+        assert!(harness.keys.held_keys().is_empty());
+    }
+}
@@ -0,0 +1,199 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    layout::Rect,
+    movement::{Clock, SystemClock},
+};
+
+// Pin settings from settings.json, e.g.
+// { "check_interval_ms": 1000, "settle_ms": 2000, "tolerance": 2 }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PinSettings {
+    // How often window rectangles are compared with their slots
+    pub check_interval_ms: u64,
+    // How long a window must have stayed out of place, without moving, before it is put
+    // back. Keeps the tool from fighting a window that is being dragged or resized.
+    pub settle_ms: u64,
+    // Pixels a window may be off by in any direction and still count as in place
+    pub tolerance: i32,
+}
+
+impl Default for PinSettings {
+    fn default() -> Self {
+        Self {
+            check_interval_ms: 1000,
+            settle_ms: 2000,
+            tolerance: 2,
+        }
+    }
+}
+
+impl PinSettings {
+    pub fn check_interval(&self) -> Duration {
+        Duration::from_millis(self.check_interval_ms)
+    }
+
+    fn settle(&self) -> Duration {
+        Duration::from_millis(self.settle_ms)
+    }
+
+    fn matches(&self, a: &Rect, b: &Rect) -> bool {
+        (a.x - b.x).abs() <= self.tolerance
+            && (a.y - b.y).abs() <= self.tolerance
+            && (a.width - b.width).abs() <= self.tolerance
+            && (a.height - b.height).abs() <= self.tolerance
+    }
+}
+
+// Decides when a window that left its slot is moved back
+pub struct PinState<C: Clock = SystemClock> {
+    clock: C,
+    // Where the window was last seen out of place and since when it has been there
+    out_of_place: Option<(Rect, Instant)>,
+}
+
+impl PinState {
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<C: Clock> PinState<C> {
+    pub fn with_clock(clock: C) -> Self {
+        Self {
+            clock,
+            out_of_place: None,
+        }
+    }
+
+    // Returns whether the window should be put back where it belongs
+    pub fn observe(&mut self, actual: Rect, intended: Rect, settings: &PinSettings) -> bool {
+        if settings.matches(&actual, &intended) {
+            self.out_of_place = None;
+            return false;
+        }
+        let now = self.clock.now();
+        match self.out_of_place {
+            // Still where it was, put it back once it has settled there
+            Some((last, since)) if settings.matches(&actual, &last) => {
+                if now.duration_since(since) >= settings.settle() {
+                    self.out_of_place = None;
+                    return true;
+                }
+            }
+            // Just left its slot or still moving
+            _ => self.out_of_place = Some((actual, now)),
+        }
+        false
+    }
+
+    // Forget a move in progress, e.g. while the mouse button is held
+    pub fn reset(&mut self) {
+        self.out_of_place = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movement::SimulatedClock;
+
+    const SLOT: Rect = Rect {
+        x: 0,
+        y: 0,
+        width: 1280,
+        height: 720,
+    };
+
+    fn moved(dx: i32, dy: i32) -> Rect {
+        Rect {
+            x: SLOT.x + dx,
+            y: SLOT.y + dy,
+            ..SLOT
+        }
+    }
+
+    fn resized(dw: i32) -> Rect {
+        Rect {
+            width: SLOT.width + dw,
+            ..SLOT
+        }
+    }
+
+    #[test]
+    fn window_within_tolerance_stays_where_it_is() {
+        let settings = PinSettings::default();
+        let clock = SimulatedClock::new();
+        let mut pin = PinState::with_clock(clock.clone());
+        for actual in [moved(2, -2), moved(-2, 0), resized(2), resized(-2)] {
+            for _ in 0..5 {
+                assert!(!pin.observe(actual, SLOT, &settings), "{:?}", actual);
+                clock.advance(settings.settle());
+            }
+        }
+    }
+
+    #[test]
+    fn window_past_tolerance_is_put_back_once_settled() {
+        let settings = PinSettings::default();
+        for actual in [moved(3, 0), moved(0, -3), resized(3)] {
+            let clock = SimulatedClock::new();
+            let mut pin = PinState::with_clock(clock.clone());
+            assert!(!pin.observe(actual, SLOT, &settings));
+
+            clock.advance(settings.settle() - Duration::from_millis(1));
+            assert!(!pin.observe(actual, SLOT, &settings), "not settled yet");
+
+            clock.advance(Duration::from_millis(1));
+            assert!(pin.observe(actual, SLOT, &settings), "{:?}", actual);
+            // Only once, the next check sees where the window went
+            assert!(!pin.observe(actual, SLOT, &settings));
+        }
+    }
+
+    #[test]
+    fn moving_window_restarts_the_wait() {
+        let settings = PinSettings::default();
+        let clock = SimulatedClock::new();
+        let mut pin = PinState::with_clock(clock.clone());
+        pin.observe(moved(50, 0), SLOT, &settings);
+
+        clock.advance(settings.settle() / 2);
+        // Jitter within the tolerance of where it was still counts as staying put
+        pin.observe(moved(52, 0), SLOT, &settings);
+        clock.advance(settings.settle() / 2);
+        assert!(pin.observe(moved(51, 0), SLOT, &settings));
+
+        pin.observe(moved(100, 0), SLOT, &settings);
+        clock.advance(settings.settle() / 2);
+        pin.observe(moved(200, 0), SLOT, &settings);
+        clock.advance(settings.settle() / 2);
+        assert!(
+            !pin.observe(moved(200, 0), SLOT, &settings),
+            "the window moved halfway through"
+        );
+        clock.advance(settings.settle() / 2);
+        assert!(pin.observe(moved(200, 0), SLOT, &settings));
+    }
+
+    #[test]
+    fn returning_to_the_slot_or_a_reset_forgets_the_move() {
+        let settings = PinSettings::default();
+        let clock = SimulatedClock::new();
+        let mut pin = PinState::with_clock(clock.clone());
+
+        pin.observe(moved(50, 0), SLOT, &settings);
+        clock.advance(settings.settle());
+        pin.observe(SLOT, SLOT, &settings);
+        assert!(!pin.observe(moved(50, 0), SLOT, &settings));
+
+        clock.advance(settings.settle());
+        pin.reset();
+        assert!(!pin.observe(moved(50, 0), SLOT, &settings));
+        clock.advance(settings.settle());
+        assert!(pin.observe(moved(50, 0), SLOT, &settings));
+    }
+}
@@ -0,0 +1,435 @@
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    injector::KeyInjector,
+    keystate::KeyStateTracker,
+    movement::{Clock, MovementController, SystemClock},
+    protocol::{macro_key, target_chord, ControlCommand, Feedback, MovementPixel, ALIVE_INTERVAL},
+};
+
+// Loops between two macro key presses
+const LOOPS_BETWEEN_KEYPRESSES: u32 = 30;
+
+// One capture of a game window and the tool state it is acted on with.
+// Everything process_window decides is decided from these, so a recording of them can be
+// replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Frame {
+    // Colors of the command and movement pixels, None when the sentinel pixel was not found
+    pub pixels: Option<(u32, u32)>,
+    pub broadcast_enabled: bool,
+    pub paused: bool,
+    // Whether the window was in the foreground
+    pub foreground: bool,
+}
+
+// Carries out the control commands an addon shows
+pub trait ControlHandler {
+    // Returns the feedback that tells the addon whether the command was carried out
+    fn handle(&self, command: ControlCommand, keys_enabled: &mut bool) -> Feedback;
+}
+
+// What a frame led to, for the dashboard
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameOutcome {
+    // A new control command and the feedback it got
+    pub control_command: Option<(ControlCommand, Feedback)>,
+    pub macro_pressed: bool,
+}
+
+// Decides the keys to send to one window, frame by frame
+pub struct WindowPipeline<C: Clock + Clone = SystemClock> {
+    label: String,
+    clock: C,
+    // SendInput focuses the window for every key, so those windows only get alive
+    // feedback while they are in the foreground anyway
+    focuses_for_input: bool,
+    keys_enabled: bool,
+    loops_since_last_keypress: u32,
+    movement: MovementController<C>,
+    last_control_command: u8,
+    addon_seen: bool,
+    last_alive_time: Instant,
+}
+
+impl WindowPipeline {
+    pub fn new(label: &str, focuses_for_input: bool) -> Self {
+        Self::with_clock(label, focuses_for_input, SystemClock)
+    }
+}
+
+impl<C: Clock + Clone> WindowPipeline<C> {
+    pub fn with_clock(label: &str, focuses_for_input: bool, clock: C) -> Self {
+        Self {
+            label: label.to_string(),
+            focuses_for_input,
+            keys_enabled: true,
+            loops_since_last_keypress: 0,
+            movement: MovementController::with_clock(clock.clone()),
+            last_control_command: 0,
+            addon_seen: false,
+            last_alive_time: clock.now(),
+            clock,
+        }
+    }
+
+    pub fn process(
+        &mut self,
+        frame: &Frame,
+        injector: &KeyStateTracker,
+        control: &dyn ControlHandler,
+    ) -> FrameOutcome {
+        let mut outcome = FrameOutcome::default();
+        match frame.pixels {
+            Some((actual_color, movement_rotation_color)) => {
+                let blue = ((actual_color >> 16) & 0xFF) as u8;
+                let green = ((actual_color >> 8) & 0xFF) as u8;
+                let red = (actual_color & 0xFF) as u8;
+                let movement_pixel = MovementPixel::decode(movement_rotation_color);
+                let now = self.clock.now();
+
+                // Tell a newly loaded addon the tool is running and what it broadcasts
+                if !self.addon_seen {
+                    self.addon_seen = true;
+                    injector.tap(Feedback::broadcast(frame.broadcast_enabled).key());
+                    injector.tap(Feedback::Alive.key());
+                    self.last_alive_time = now;
+                } else if now.duration_since(self.last_alive_time) >= ALIVE_INTERVAL
+                    && (!self.focuses_for_input || frame.foreground)
+                {
+                    injector.tap(Feedback::Alive.key());
+                    self.last_alive_time = now;
+                }
+
+                // The addon shows a command until it gets feedback for it, so act only
+                // when it changes
+                if blue != self.last_control_command {
+                    self.last_control_command = blue;
+                    if let Some(command) = ControlCommand::decode(blue) {
+                        let feedback = control.handle(command, &mut self.keys_enabled);
+                        injector.tap(feedback.key());
+                        outcome.control_command = Some((command, feedback));
+                    }
+                }
+
+                if frame.paused {
+                    // Control commands are still read, but nothing stays held
+                    self.movement.release(injector);
+                    if !injector.held_keys().is_empty() {
+                        injector.release_all();
+                    }
+                } else {
+                    self.movement.update(injector, movement_pixel);
+                }
+
+                if self.keys_enabled
+                    && !frame.paused
+                    && self.loops_since_last_keypress >= LOOPS_BETWEEN_KEYPRESSES
+                    && press_macro_key(injector, red, green)
+                {
+                    // Reset loop counter on keypress
+                    self.loops_since_last_keypress = 0;
+                    outcome.macro_pressed = true;
+                }
+            }
+            // The addon can't be seen (UI reload, Lua error, capture failure),
+            // so nothing it asked for should stay held
+            None => {
+                self.movement.release(injector);
+                if !injector.held_keys().is_empty() {
+                    println!("[{}] Addon lost, releasing held keys", self.label);
+                    injector.release_all();
+                }
+                self.last_control_command = 0;
+                self.addon_seen = false;
+            }
+        }
+        self.loops_since_last_keypress += 1;
+        outcome
+    }
+}
+
+// Press the macro key shown by the addon on the target it shows
+fn press_macro_key(injector: &dyn KeyInjector, red: u8, green: u8) -> bool {
+    let Some(vk) = macro_key(red) else {
+        return false;
+    };
+    if let Some((modifier, numpad_key)) = target_chord(green) {
+        injector.chord(modifier, numpad_key);
+    }
+    injector.tap(vk);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, sync::Arc, time::Duration};
+
+    use super::*;
+    use crate::{
+        injector::{InjectedKey, RecordingInjector},
+        movement::{SimulatedClock, MOVE_FORWARD},
+    };
+
+    const VK_UP: u16 = 0x26;
+    const VK_A: u16 = 0x41;
+
+    // Carries out every command with the same feedback and remembers what it was asked
+    struct FakeControl {
+        feedback: Feedback,
+        handled: RefCell<Vec<ControlCommand>>,
+    }
+
+    impl FakeControl {
+        fn new(feedback: Feedback) -> Self {
+            Self {
+                feedback,
+                handled: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl ControlHandler for FakeControl {
+        fn handle(&self, command: ControlCommand, keys_enabled: &mut bool) -> Feedback {
+            self.handled.borrow_mut().push(command);
+            match command {
+                ControlCommand::KeysDisabled => *keys_enabled = false,
+                ControlCommand::KeysEnabled => *keys_enabled = true,
+                _ => {}
+            }
+            self.feedback
+        }
+    }
+
+    struct Harness {
+        clock: SimulatedClock,
+        injector: Arc<RecordingInjector>,
+        keys: KeyStateTracker,
+        pipeline: WindowPipeline<SimulatedClock>,
+        control: FakeControl,
+    }
+
+    impl Harness {
+        fn new(focuses_for_input: bool) -> Self {
+            let clock = SimulatedClock::new();
+            let injector = Arc::new(RecordingInjector::silent());
+            Self {
+                keys: KeyStateTracker::new(Box::new(injector.clone())),
+                injector,
+                pipeline: WindowPipeline::with_clock("test", focuses_for_input, clock.clone()),
+                control: FakeControl::new(Feedback::CommandExecuted),
+                clock,
+            }
+        }
+
+        fn process(&mut self, frame: Frame) -> FrameOutcome {
+            self.pipeline.process(&frame, &self.keys, &self.control)
+        }
+
+        // A frame showing the command in the blue channel and the movement bitmask
+        fn show(&mut self, blue: u8, bitmask: u8) -> FrameOutcome {
+            self.process(frame(Some(((blue as u32) << 16, bitmask as u32))))
+        }
+
+        fn taps_of(&self, vk: u16) -> usize {
+            self.injector
+                .events()
+                .iter()
+                .filter(|event| event.vk == vk && event.down)
+                .count()
+        }
+    }
+
+    fn frame(pixels: Option<(u32, u32)>) -> Frame {
+        Frame {
+            pixels,
+            broadcast_enabled: false,
+            paused: false,
+            foreground: true,
+        }
+    }
+
+    #[test]
+    fn acts_once_per_shown_command() {
+        let mut harness = Harness::new(false);
+        for _ in 0..3 {
+            harness.show(200, 0);
+        }
+        assert_eq!(
+            *harness.control.handled.borrow(),
+            vec![ControlCommand::ReleaseAll],
+            "a command still on screen must not be carried out again"
+        );
+
+        // A different command right after is acted on without a clear in between
+        harness.show(3, 0);
+        harness.show(4, 0);
+        assert_eq!(harness.control.handled.borrow().len(), 3);
+    }
+
+    #[test]
+    fn repeats_a_command_shown_again_after_a_clear() {
+        let mut harness = Harness::new(false);
+        harness.show(200, 0);
+        harness.show(0, 0);
+        harness.show(0, 0);
+        harness.show(200, 0);
+        assert_eq!(
+            *harness.control.handled.borrow(),
+            vec![ControlCommand::ReleaseAll, ControlCommand::ReleaseAll]
+        );
+        assert_eq!(harness.taps_of(Feedback::CommandExecuted.key()), 2);
+    }
+
+    #[test]
+    fn repeats_a_command_shown_again_after_the_addon_was_lost() {
+        let mut harness = Harness::new(false);
+        harness.show(200, 0);
+        harness.process(frame(None));
+        harness.show(200, 0);
+        assert_eq!(harness.control.handled.borrow().len(), 2);
+    }
+
+    #[test]
+    fn acknowledges_every_command_with_the_handler_feedback() {
+        for feedback in [Feedback::CommandExecuted, Feedback::CommandFailed] {
+            let mut harness = Harness::new(false);
+            harness.control = FakeControl::new(feedback);
+            harness.show(0, 0);
+            let before = harness.injector.events().len();
+
+            let outcome = harness.show(105, 0);
+            let command = ControlCommand::Focus {
+                omb_number: 6,
+                into_main: false,
+            };
+            assert_eq!(outcome.control_command, Some((command, feedback)));
+            assert_eq!(
+                harness.injector.events()[before..],
+                [
+                    InjectedKey {
+                        vk: feedback.key(),
+                        down: true,
+                    },
+                    InjectedKey {
+                        vk: feedback.key(),
+                        down: false,
+                    },
+                ],
+                "only the feedback key is tapped"
+            );
+
+            // The addon clears the command once it got the feedback
+            assert_eq!(harness.show(0, 0).control_command, None);
+        }
+    }
+
+    #[test]
+    fn handler_can_disable_macro_presses() {
+        let mut harness = Harness::new(false);
+        harness.show(1, 0);
+        let macro_frame = frame(Some((0x01, 0)));
+        for _ in 0..LOOPS_BETWEEN_KEYPRESSES * 2 {
+            assert!(!harness.process(macro_frame).macro_pressed);
+        }
+        harness.show(2, 0);
+        let pressed = (0..LOOPS_BETWEEN_KEYPRESSES * 2)
+            .filter(|_| harness.process(macro_frame).macro_pressed)
+            .count();
+        assert!(pressed > 0, "keys were enabled again");
+    }
+
+    #[test]
+    fn sends_alive_feedback_every_interval() {
+        let mut harness = Harness::new(false);
+        let alive = Feedback::Alive.key();
+
+        // A newly seen addon is told right away, together with the broadcast state
+        harness.show(0, 0);
+        assert_eq!(harness.taps_of(alive), 1);
+        assert_eq!(harness.taps_of(Feedback::BroadcastOff.key()), 1);
+
+        harness
+            .clock
+            .advance(ALIVE_INTERVAL - Duration::from_millis(100));
+        harness.show(0, 0);
+        assert_eq!(harness.taps_of(alive), 1, "too early for alive feedback");
+
+        harness.clock.advance(Duration::from_millis(100));
+        harness.show(0, 0);
+        assert_eq!(harness.taps_of(alive), 2);
+
+        for _ in 0..3 {
+            harness.clock.advance(ALIVE_INTERVAL);
+            harness.show(0, 0);
+        }
+        assert_eq!(harness.taps_of(alive), 5);
+    }
+
+    #[test]
+    fn windows_focused_for_input_get_alive_feedback_only_in_the_foreground() {
+        let mut harness = Harness::new(true);
+        let alive = Feedback::Alive.key();
+        let background = Frame {
+            foreground: false,
+            ..frame(Some((0, 0)))
+        };
+        harness.process(background);
+        assert_eq!(harness.taps_of(alive), 1);
+
+        harness.clock.advance(ALIVE_INTERVAL * 2);
+        harness.process(background);
+        assert_eq!(harness.taps_of(alive), 1);
+
+        harness.show(0, 0);
+        assert_eq!(harness.taps_of(alive), 2);
+    }
+
+    #[test]
+    fn releases_held_keys_when_the_addon_is_lost() {
+        let mut harness = Harness::new(false);
+        harness.show(0, MOVE_FORWARD);
+        harness.keys.key_down(VK_A);
+        assert_eq!(harness.keys.held_keys(), vec![VK_UP, VK_A]);
+
+        harness.process(frame(None));
+        assert!(harness.keys.held_keys().is_empty());
+        assert!(harness.injector.events().ends_with(&[
+            InjectedKey {
+                vk: VK_UP,
+                down: false
+            },
+            InjectedKey {
+                vk: VK_A,
+                down: false
+            }
+        ]));
+    }
+
+    #[test]
+    fn releases_held_keys_on_pause_but_still_reads_commands() {
+        let mut harness = Harness::new(false);
+        harness.show(0, MOVE_FORWARD);
+        harness.keys.key_down(VK_A);
+
+        let paused = Frame {
+            paused: true,
+            ..frame(Some((200 << 16, MOVE_FORWARD as u32)))
+        };
+        harness.process(paused);
+        assert!(harness.keys.held_keys().is_empty());
+        assert_eq!(
+            *harness.control.handled.borrow(),
+            vec![ControlCommand::ReleaseAll]
+        );
+
+        // Nothing is pressed again while paused
+        harness.process(paused);
+        assert!(harness.keys.held_keys().is_empty());
+
+        harness.show(0, MOVE_FORWARD);
+        assert_eq!(harness.keys.held_keys(), vec![VK_UP]);
+    }
+}
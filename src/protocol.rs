@@ -0,0 +1,354 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+// How the addon and the tool talk to each other.
+//
+// Addon -> tool: pixels drawn in the top-left corner of the game window. The command pixel's
+// blue channel carries a control command, shown until the tool sends feedback for it.
+// Tool -> addon: taps of reserved keys, each bound by the addon to a feedback handler.
+
+// In-game addon sets sentinel pixels to check if the addon is active.
+// Lua (r=0x12, g=0x34, b=0x56) -> BGR 0x563412
+pub const SENTINEL_COLOR: u32 = 0x563412;
+
+// How often the tool tells each addon it is still running
+pub const ALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+// Function keys pressed for the macro number in the red channel of the command pixel, 1-25.
+// Both 18 and 19 press F18, bind macros accordingly.
+const MACRO_KEYS: [u16; 25] = [
+    0x70, 0x71, 0x72, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x7B, // F1-F12
+    0x7C, 0x7D, 0x7E, 0x7F, 0x80, 0x81, 0x81, 0x82, 0x83, 0x84, 0x85, 0x86, // F13-F23
+    0x87, // F24
+];
+
+// Virtual-key code of the macro key for the red channel, None when no macro is shown
+pub fn macro_key(red: u8) -> Option<u16> {
+    MACRO_KEYS.get((red as usize).checked_sub(1)?).copied()
+}
+
+// Key chord that targets a group member, from the green channel of the command pixel.
+// Targets 1-40 are the numpad keys, each with no modifier, CTRL, SHIFT or ALT. Anything
+// above would run into the numpad operator keys used for feedback.
+pub fn target_chord(green: u8) -> Option<(Option<u16>, u16)> {
+    let index = green.checked_sub(1).filter(|&index| index < 40)?;
+    let modifier = match index % 4 {
+        1 => Some(0xA2), // VK_LCONTROL
+        2 => Some(0xA0), // VK_LSHIFT
+        3 => Some(0xA4), // VK_LMENU
+        _ => None,
+    };
+    let numpad_key = 0x60 + (index / 4) as u16; // VK_NUMPAD0 onwards
+    Some((modifier, numpad_key))
+}
+
+// Control commands carried in the blue channel of the command pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlCommand {
+    // 1/2: the addon disabled/enabled its own key presses
+    KeysDisabled,
+    KeysEnabled,
+    // 3/4
+    BroadcastOn,
+    BroadcastOff,
+    // 5-99: swap layout slots with OMB (blue - 4)
+    SwapWith(usize),
+    // 100-149: focus OMB (blue - 99), 150-199: focus OMB (blue - 149) and swap it into
+    // the main slot
+    Focus { omb_number: usize, into_main: bool },
+    // 200: release all keys held by the tool
+    ReleaseAll,
+    Unknown(u8),
+}
+
+impl ControlCommand {
+    // Decode the blue channel of the command pixel, None when no command is shown
+    pub fn decode(blue: u8) -> Option<Self> {
+        let command = match blue {
+            0 => return None,
+            1 => Self::KeysDisabled,
+            2 => Self::KeysEnabled,
+            3 => Self::BroadcastOn,
+            4 => Self::BroadcastOff,
+            5..=99 => Self::SwapWith((blue - 4) as usize),
+            100..=149 => Self::Focus {
+                omb_number: (blue - 99) as usize,
+                into_main: false,
+            },
+            150..=199 => Self::Focus {
+                omb_number: (blue - 149) as usize,
+                into_main: true,
+            },
+            200 => Self::ReleaseAll,
+            _ => Self::Unknown(blue),
+        };
+        Some(command)
+    }
+}
+
+// Feedback the tool sends to an addon by tapping a key in its window.
+// Multibox.lua binds the same keys in FEEDBACK_KEYS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feedback {
+    // The control command on screen was carried out, the addon stops showing it
+    CommandExecuted,
+    // The control command on screen could not be carried out, the addon stops showing it
+    CommandFailed,
+    // The tool's broadcast state, sent to every window when it changes and when an
+    // addon is first seen
+    BroadcastOn,
+    BroadcastOff,
+    // Sent every ALIVE_INTERVAL so the addon can tell the tool is running
+    Alive,
+}
+
+impl Feedback {
+    pub fn broadcast(enabled: bool) -> Self {
+        if enabled {
+            Self::BroadcastOn
+        } else {
+            Self::BroadcastOff
+        }
+    }
+
+    // Virtual-key code tapped for this feedback.
+    // Numpad operator keys are not used by any class bindings.
+    pub fn key(self) -> u16 {
+        match self {
+            Self::CommandExecuted => 0x6F, // NUMPADDIVIDE
+            Self::CommandFailed => 0x6E,   // NUMPADDECIMAL
+            Self::BroadcastOn => 0x6A,     // NUMPADMULTIPLY
+            Self::BroadcastOff => 0x6D,    // NUMPADMINUS
+            Self::Alive => 0x6B,           // NUMPADPLUS
+        }
+    }
+}
+
+// Steps of the analog values in the movement pixel. Multibox.lua encodes with the same steps.
+pub const HEADING_STEP: f32 = std::f32::consts::PI / 127.0; // Radians
+pub const DISTANCE_STEP: f32 = 0.25; // Yards
+
+// Farthest distance the movement pixel can carry, farther targets are drawn as this
+pub const MAX_DISTANCE: f32 = 127.0 * DISTANCE_STEP;
+
+// Bit of the movement pixel's red channel the addon flips several times a second, so a
+// frozen addon can be told apart from an idle one
+pub const HEARTBEAT_BIT: u8 = 0x80;
+
+// The movement pixel at (2,0).
+// Red is the movement/rotation bitmask, kept for addons that only draw that, and the
+// heartbeat bit.
+// Green is the signed heading error to the target facing, positive = turn left.
+// Blue is the signed distance to the target along the facing, positive = ahead.
+// Green and blue are 0 when not set, otherwise 128 plus the value in steps.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MovementPixel {
+    pub bitmask: u8,
+    pub heartbeat: bool,
+    pub heading_error: Option<f32>,
+    pub distance: Option<f32>,
+}
+
+impl MovementPixel {
+    // Decode the captured color, 0x00BBGGRR
+    pub fn decode(color: u32) -> Self {
+        Self {
+            bitmask: (color & 0xFF) as u8 & !HEARTBEAT_BIT,
+            heartbeat: (color & 0xFF) as u8 & HEARTBEAT_BIT != 0,
+            heading_error: decode_signed(((color >> 8) & 0xFF) as u8, HEADING_STEP),
+            distance: decode_signed(((color >> 16) & 0xFF) as u8, DISTANCE_STEP),
+        }
+    }
+}
+
+fn decode_signed(value: u8, step: f32) -> Option<f32> {
+    if value == 0 {
+        None
+    } else {
+        Some((value as i32 - 128) as f32 * step)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The movement pixel color Multibox.lua draws, 0x00BBGGRR
+    fn color(red: u8, green: u8, blue: u8) -> u32 {
+        red as u32 | (green as u32) << 8 | (blue as u32) << 16
+    }
+
+    #[test]
+    fn decodes_every_control_command() {
+        for blue in 0..=255u8 {
+            let expected = match blue {
+                0 => None,
+                1 => Some(ControlCommand::KeysDisabled),
+                2 => Some(ControlCommand::KeysEnabled),
+                3 => Some(ControlCommand::BroadcastOn),
+                4 => Some(ControlCommand::BroadcastOff),
+                5..=99 => Some(ControlCommand::SwapWith(blue as usize - 4)),
+                100..=149 => Some(ControlCommand::Focus {
+                    omb_number: blue as usize - 99,
+                    into_main: false,
+                }),
+                150..=199 => Some(ControlCommand::Focus {
+                    omb_number: blue as usize - 149,
+                    into_main: true,
+                }),
+                200 => Some(ControlCommand::ReleaseAll),
+                _ => Some(ControlCommand::Unknown(blue)),
+            };
+            assert_eq!(ControlCommand::decode(blue), expected, "blue {}", blue);
+        }
+    }
+
+    #[test]
+    fn window_numbers_start_at_1() {
+        let cases = [
+            (5, ControlCommand::SwapWith(1)),
+            (99, ControlCommand::SwapWith(95)),
+            (
+                100,
+                ControlCommand::Focus {
+                    omb_number: 1,
+                    into_main: false,
+                },
+            ),
+            (
+                149,
+                ControlCommand::Focus {
+                    omb_number: 50,
+                    into_main: false,
+                },
+            ),
+            (
+                150,
+                ControlCommand::Focus {
+                    omb_number: 1,
+                    into_main: true,
+                },
+            ),
+            (
+                199,
+                ControlCommand::Focus {
+                    omb_number: 50,
+                    into_main: true,
+                },
+            ),
+        ];
+        for (blue, command) in cases {
+            assert_eq!(ControlCommand::decode(blue), Some(command), "blue {}", blue);
+        }
+    }
+
+    #[test]
+    fn feedback_keys_match_the_addon_bindings() {
+        let cases = [
+            (Feedback::CommandExecuted, 0x6F),
+            (Feedback::CommandFailed, 0x6E),
+            (Feedback::BroadcastOn, 0x6A),
+            (Feedback::BroadcastOff, 0x6D),
+            (Feedback::Alive, 0x6B),
+        ];
+        for (feedback, vk) in cases {
+            assert_eq!(feedback.key(), vk, "{:?}", feedback);
+        }
+        assert_eq!(Feedback::broadcast(true), Feedback::BroadcastOn);
+        assert_eq!(Feedback::broadcast(false), Feedback::BroadcastOff);
+    }
+
+    #[test]
+    fn maps_every_macro_number_to_a_function_key() {
+        assert_eq!(macro_key(0), None);
+        for red in 1..=17u8 {
+            assert_eq!(macro_key(red), Some(0x70 + red as u16 - 1), "red {}", red);
+        }
+        // 18 and 19 share F18, the rest are one key further on
+        assert_eq!(macro_key(18), Some(0x81));
+        assert_eq!(macro_key(19), Some(0x81));
+        for red in 20..=25u8 {
+            assert_eq!(macro_key(red), Some(0x70 + red as u16 - 2), "red {}", red);
+        }
+        for red in 26..=255u8 {
+            assert_eq!(macro_key(red), None, "red {}", red);
+        }
+    }
+
+    #[test]
+    fn maps_every_target_to_a_numpad_chord() {
+        assert_eq!(target_chord(0), None);
+        let modifiers = [None, Some(0xA2), Some(0xA0), Some(0xA4)];
+        for green in 1..=40u8 {
+            let index = green - 1;
+            assert_eq!(
+                target_chord(green),
+                Some((modifiers[index as usize % 4], 0x60 + index as u16 / 4)),
+                "green {}",
+                green
+            );
+        }
+        assert_eq!(target_chord(40), Some((Some(0xA4), 0x69)));
+        for green in 41..=255u8 {
+            assert_eq!(target_chord(green), None, "green {}", green);
+        }
+    }
+
+    #[test]
+    fn decodes_every_channel() {
+        let pixel = MovementPixel::decode(color(0x80 | 0x05, 128 + 10, 128 - 8));
+        assert_eq!(
+            pixel,
+            MovementPixel {
+                bitmask: 0x05,
+                heartbeat: true,
+                heading_error: Some(10.0 * HEADING_STEP),
+                distance: Some(-8.0 * DISTANCE_STEP),
+            }
+        );
+    }
+
+    #[test]
+    fn signed_values_round_trip_in_steps() {
+        for steps in -127..=127i32 {
+            let encoded = (steps + 128) as u8;
+            let pixel = MovementPixel::decode(color(0, encoded, encoded));
+            assert_eq!(pixel.heading_error, Some(steps as f32 * HEADING_STEP));
+            assert_eq!(pixel.distance, Some(steps as f32 * DISTANCE_STEP));
+        }
+    }
+
+    #[test]
+    fn signed_values_span_half_a_turn_and_32_yards() {
+        let pixel = MovementPixel::decode(color(0, 255, 1));
+        assert!((pixel.heading_error.unwrap() - std::f32::consts::PI).abs() < 1e-6);
+        assert_eq!(pixel.distance, Some(-31.75));
+    }
+
+    #[test]
+    fn center_value_is_zero() {
+        let pixel = MovementPixel::decode(color(0, 128, 128));
+        assert_eq!(pixel.heading_error, Some(0.0));
+        assert_eq!(pixel.distance, Some(0.0));
+    }
+
+    #[test]
+    fn zero_channels_are_absent() {
+        assert_eq!(MovementPixel::decode(0), MovementPixel::default());
+        let pixel = MovementPixel::decode(color(0x04, 0, 200));
+        assert_eq!(pixel.heading_error, None);
+        assert_eq!(pixel.distance, Some(72.0 * DISTANCE_STEP));
+    }
+
+    #[test]
+    fn heartbeat_bit_is_not_a_movement_bit() {
+        let pixel = MovementPixel::decode(color(HEARTBEAT_BIT, 0, 0));
+        assert!(pixel.heartbeat);
+        assert_eq!(pixel.bitmask, 0);
+        let pixel = MovementPixel::decode(color(0x3F, 0, 0));
+        assert!(!pixel.heartbeat);
+        assert_eq!(pixel.bitmask, 0x3F);
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    fs::File,
+    io::{LineWriter, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    injector::{InjectionMethod, KeyInjector},
+    pipeline::Frame,
+    protocol::Feedback,
+};
+
+// One line of a recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    // Registration of the window the event belongs to. Every window the tool starts
+    // handling gets a new one, so windows with the same title are told apart.
+    pub registration: u64,
+    // Microseconds since the recording started
+    pub elapsed_us: u64,
+    #[serde(flatten)]
+    pub event: RecordedEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RecordedEvent {
+    // The tool started handling the window
+    Window {
+        title: String,
+        injection_method: InjectionMethod,
+    },
+    // A capture of the window, once per loop
+    Frame(Frame),
+    // The outcome of a control command, which depends on the other windows
+    Command {
+        feedback: Feedback,
+    },
+    // A key event sent to the window, from its own loop or from elsewhere in the tool
+    Key {
+        vk: u16,
+        down: bool,
+    },
+    // A key typed in the focused window and posted to this one while broadcasting. Posted
+    // keys bypass the window's key state, so replaying doesn't send them again.
+    Broadcast {
+        vk: u16,
+        down: bool,
+    },
+}
+
+// Writes records as JSON lines, flushed line by line so a crash keeps everything before it
+pub struct Recorder {
+    started: Instant,
+    next_registration: AtomicU64,
+    file: Mutex<LineWriter<File>>,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Option<Self> {
+        match File::create(path) {
+            Ok(file) => {
+                println!("Recording to {}", path);
+                Some(Self {
+                    started: Instant::now(),
+                    next_registration: AtomicU64::new(1),
+                    file: Mutex::new(LineWriter::new(file)),
+                })
+            }
+            Err(e) => {
+                println!("Failed to create recording {}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    // Start recording a window the tool started handling
+    pub fn start_window(
+        &'static self,
+        title: &str,
+        injection_method: InjectionMethod,
+    ) -> WindowRecording {
+        let recording = WindowRecording {
+            recorder: self,
+            registration: self.next_registration.fetch_add(1, Ordering::SeqCst),
+        };
+        recording.record(RecordedEvent::Window {
+            title: title.to_string(),
+            injection_method,
+        });
+        recording
+    }
+
+    fn record(&self, registration: u64, event: RecordedEvent) {
+        let record = Record {
+            registration,
+            elapsed_us: self.started.elapsed().as_micros() as u64,
+            event,
+        };
+        match serde_json::to_string(&record) {
+            Ok(line) => {
+                let mut file = self.file.lock().unwrap();
+                if let Err(e) = writeln!(file, "{}", line) {
+                    println!("Failed to write recording: {}", e);
+                }
+            }
+            Err(e) => println!("Failed to serialize record: {}", e),
+        }
+    }
+}
+
+// Where the events of one registered window are recorded
+#[derive(Clone, Copy)]
+pub struct WindowRecording {
+    recorder: &'static Recorder,
+    registration: u64,
+}
+
+impl WindowRecording {
+    pub fn record(&self, event: RecordedEvent) {
+        self.recorder.record(self.registration, event);
+    }
+}
+
+// Records every key event before passing it on to the window's injector
+pub struct KeyRecorder {
+    injector: Box<dyn KeyInjector>,
+    recording: WindowRecording,
+}
+
+impl KeyRecorder {
+    // Wrap an injector when recording, or leave it as it is
+    pub fn wrap(
+        injector: Box<dyn KeyInjector>,
+        recording: Option<WindowRecording>,
+    ) -> Box<dyn KeyInjector> {
+        match recording {
+            Some(recording) => Box::new(Self {
+                injector,
+                recording,
+            }),
+            None => injector,
+        }
+    }
+
+    fn record(&self, vk: u16, down: bool) {
+        self.recording.record(RecordedEvent::Key { vk, down });
+    }
+}
+
+impl KeyInjector for KeyRecorder {
+    fn key_down(&self, vk: u16) {
+        self.record(vk, true);
+        self.injector.key_down(vk);
+    }
+
+    fn key_up(&self, vk: u16) {
+        self.record(vk, false);
+        self.injector.key_up(vk);
+    }
+
+    // Taps and chords are passed on whole, some injectors send them in one batch
+    fn tap(&self, vk: u16) {
+        self.record(vk, true);
+        self.record(vk, false);
+        self.injector.tap(vk);
+    }
+
+    fn chord(&self, modifier: Option<u16>, vk: u16) {
+        if let Some(modifier) = modifier {
+            self.record(modifier, true);
+        }
+        self.record(vk, true);
+        self.record(vk, false);
+        if let Some(modifier) = modifier {
+            self.record(modifier, false);
+        }
+        self.injector.chord(modifier, vk);
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+// Which game window has which OMB number. A window is given its number when it is found
+// and keeps it until it closes, whatever happens to its title in the meantime. The title
+// only shows the number.
+#[derive(Debug, Clone)]
+pub struct WindowRegistry<W> {
+    // OMB number -> window, numbers start at 1
+    windows: BTreeMap<usize, W>,
+}
+
+impl<W> Default for WindowRegistry<W> {
+    fn default() -> Self {
+        Self {
+            windows: BTreeMap::new(),
+        }
+    }
+}
+
+impl<W: Copy + PartialEq> WindowRegistry<W> {
+    // Give a window an OMB number: the one it asks for when that is free, e.g. the one in
+    // its title so windows keep their numbers across restarts, or else the lowest free one
+    // that isn't reserved. Windows that already have a number keep it.
+    pub fn register(&mut self, window: W, requested: Option<usize>, reserved: &[usize]) -> usize {
+        if let Some(number) = self.number_of(window) {
+            return number;
+        }
+        let number = match requested {
+            Some(number) if number > 0 && !self.windows.contains_key(&number) => number,
+            _ => (1..)
+                .find(|number| !self.windows.contains_key(number) && !reserved.contains(number))
+                .unwrap(),
+        };
+        self.windows.insert(number, window);
+        number
+    }
+
+    // Free the number of a window that went away
+    pub fn unregister(&mut self, window: W) -> Option<usize> {
+        let number = self.number_of(window)?;
+        self.windows.remove(&number);
+        Some(number)
+    }
+
+    pub fn number_of(&self, window: W) -> Option<usize> {
+        self.windows
+            .iter()
+            .find(|&(_, &registered)| registered == window)
+            .map(|(&number, _)| number)
+    }
+
+    pub fn window_of(&self, number: usize) -> Option<W> {
+        self.windows.get(&number).copied()
+    }
+
+    pub fn contains(&self, window: W) -> bool {
+        self.number_of(window).is_some()
+    }
+
+    // Every registered window with its OMB number, lowest number first
+    pub fn windows(&self) -> Vec<(usize, W)> {
+        self.windows
+            .iter()
+            .map(|(&number, &window)| (number, window))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gives_out_the_lowest_free_number() {
+        let mut registry = WindowRegistry::default();
+        assert_eq!(registry.register('a', None, &[]), 1);
+        assert_eq!(registry.register('b', None, &[]), 2);
+        assert_eq!(registry.register('c', None, &[]), 3);
+        assert_eq!(registry.windows(), vec![(1, 'a'), (2, 'b'), (3, 'c')]);
+    }
+
+    #[test]
+    fn reuses_the_number_of_a_closed_window() {
+        let mut registry = WindowRegistry::default();
+        registry.register('a', None, &[]);
+        registry.register('b', None, &[]);
+        registry.register('c', None, &[]);
+
+        assert_eq!(registry.unregister('b'), Some(2));
+        assert!(!registry.contains('b'));
+        assert_eq!(registry.window_of(2), None);
+        assert_eq!(registry.register('d', None, &[]), 2);
+        assert_eq!(registry.number_of('d'), Some(2));
+
+        // Closing a window twice frees nothing the second time
+        assert_eq!(registry.unregister('b'), None);
+    }
+
+    #[test]
+    fn honours_a_requested_number_when_it_is_free() {
+        let mut registry = WindowRegistry::default();
+        assert_eq!(registry.register('a', Some(4), &[]), 4);
+        // Taken, so the lowest free one instead
+        assert_eq!(registry.register('b', Some(4), &[]), 1);
+        // OMB numbers start at 1
+        assert_eq!(registry.register('c', Some(0), &[]), 2);
+    }
+
+    #[test]
+    fn skips_reserved_numbers() {
+        let mut registry = WindowRegistry::default();
+        assert_eq!(registry.register('a', None, &[1, 2]), 3);
+        // A window may still ask for a reserved number, e.g. the one in its title
+        assert_eq!(registry.register('b', Some(1), &[1, 2]), 1);
+    }
+
+    #[test]
+    fn a_registered_window_keeps_its_number() {
+        let mut registry = WindowRegistry::default();
+        registry.register('a', None, &[]);
+        assert_eq!(registry.register('b', None, &[]), 2);
+        assert_eq!(registry.register('b', Some(5), &[]), 2);
+        assert_eq!(registry.windows().len(), 2);
+    }
+}
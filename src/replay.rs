@@ -0,0 +1,374 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    injector::{InjectedKey, InjectionMethod, KeyInjector},
+    keystate::KeyStateTracker,
+    movement::SimulatedClock,
+    pipeline::{ControlHandler, WindowPipeline},
+    protocol::{ControlCommand, Feedback},
+    recorder::{Record, RecordedEvent},
+};
+
+// Replays a recording made with "record_file" in settings.json. Each window's frames are
+// fed through a fresh pipeline on a simulated clock, with a fake injector, and the keys it
+// sends are compared with the recorded ones.
+
+// Collects the keys the pipeline sends
+#[derive(Clone, Default)]
+struct ReplayInjector {
+    events: Arc<Mutex<Vec<InjectedKey>>>,
+}
+
+impl KeyInjector for ReplayInjector {
+    fn key_down(&self, vk: u16) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(InjectedKey { vk, down: true });
+    }
+
+    fn key_up(&self, vk: u16) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(InjectedKey { vk, down: false });
+    }
+}
+
+// Answers control commands with the recorded outcomes, in order
+struct RecordedOutcomes {
+    label: String,
+    feedback: RefCell<VecDeque<Feedback>>,
+}
+
+impl ControlHandler for RecordedOutcomes {
+    fn handle(&self, command: ControlCommand, _keys_enabled: &mut bool) -> Feedback {
+        self.feedback.borrow_mut().pop_front().unwrap_or_else(|| {
+            println!(
+                "[{}] No recorded outcome for {:?}, failing it",
+                self.label, command
+            );
+            Feedback::CommandFailed
+        })
+    }
+}
+
+// A key event and when it was sent, in microseconds since the recording started
+type TimedKey = (u64, InjectedKey);
+
+// Replay the recording at `path`. Returns whether every window sent the recorded keys.
+pub fn replay(path: &str) -> bool {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => replay_recording(&contents, path),
+        Err(e) => {
+            println!("Failed to read recording {}: {}", path, e);
+            false
+        }
+    }
+}
+
+fn replay_recording(contents: &str, path: &str) -> bool {
+    let mut windows: BTreeMap<u64, Vec<Record>> = BTreeMap::new();
+    for (number, line) in contents.lines().enumerate() {
+        match serde_json::from_str::<Record>(line) {
+            Ok(record) => windows.entry(record.registration).or_default().push(record),
+            // The last line may be cut short if the tool was killed
+            Err(e) => println!("Skipping line {} of {}: {}", number + 1, path, e),
+        }
+    }
+
+    let mut all_matched = true;
+    for (registration, records) in &windows {
+        all_matched &= replay_window(*registration, records);
+    }
+    all_matched
+}
+
+fn replay_window(registration: u64, records: &[Record]) -> bool {
+    let (title, injection_method) = records
+        .iter()
+        .find_map(|record| match &record.event {
+            RecordedEvent::Window {
+                title,
+                injection_method,
+            } => Some((title.as_str(), *injection_method)),
+            _ => None,
+        })
+        .unwrap_or_default();
+    let window = format!("{} #{}", title, registration);
+    let outcomes = RecordedOutcomes {
+        label: window.clone(),
+        feedback: RefCell::new(
+            records
+                .iter()
+                .filter_map(|record| match record.event {
+                    RecordedEvent::Command { feedback } => Some(feedback),
+                    _ => None,
+                })
+                .collect(),
+        ),
+    };
+    let recorded: Vec<TimedKey> = records
+        .iter()
+        .filter_map(|record| match record.event {
+            RecordedEvent::Key { vk, down } => Some((record.elapsed_us, InjectedKey { vk, down })),
+            _ => None,
+        })
+        .collect();
+    let broadcast = records
+        .iter()
+        .filter(|record| matches!(record.event, RecordedEvent::Broadcast { .. }))
+        .count();
+
+    let clock = SimulatedClock::new();
+    let keys = ReplayInjector::default();
+    let injector = KeyStateTracker::new(Box::new(keys.clone()));
+    let mut pipeline = WindowPipeline::with_clock(
+        &window,
+        injection_method == InjectionMethod::SendInput,
+        clock.clone(),
+    );
+
+    let mut replayed: Vec<TimedKey> = Vec::new();
+    let mut elapsed_us = 0;
+    let mut frames = 0;
+    for record in records {
+        let RecordedEvent::Frame(frame) = &record.event else {
+            continue;
+        };
+        clock.advance(Duration::from_micros(
+            record.elapsed_us.saturating_sub(elapsed_us),
+        ));
+        elapsed_us = elapsed_us.max(record.elapsed_us);
+        pipeline.process(frame, &injector, &outcomes);
+        frames += 1;
+        replayed.extend(
+            keys.events
+                .lock()
+                .unwrap()
+                .drain(..)
+                .map(|key| (elapsed_us, key)),
+        );
+    }
+    // The window's loop releases everything when it ends
+    injector.release_all();
+    replayed.extend(
+        keys.events
+            .lock()
+            .unwrap()
+            .drain(..)
+            .map(|key| (elapsed_us, key)),
+    );
+
+    println!(
+        "[{}] {} frames, {} key events replayed, {} recorded, {} broadcast",
+        window,
+        frames,
+        replayed.len(),
+        recorded.len(),
+        broadcast
+    );
+    let divergence = (0..replayed.len().max(recorded.len()))
+        .find(|&i| replayed.get(i).map(|(_, key)| key) != recorded.get(i).map(|(_, key)| key));
+    match divergence {
+        None => true,
+        Some(i) => {
+            println!(
+                "[{}] Key event {} differs: replayed {}, recorded {}",
+                window,
+                i + 1,
+                describe(replayed.get(i)),
+                describe(recorded.get(i))
+            );
+            // Keys sent from outside the window's loop, like follow keys, feedback to all
+            // windows and releasing every window's keys, are only in the recording
+            println!(
+                "[{}] Keys sent from outside the window's own loop only show in the recording",
+                window
+            );
+            false
+        }
+    }
+}
+
+fn describe(key: Option<&TimedKey>) -> String {
+    match key {
+        Some((elapsed_us, key)) => format!(
+            "key {} {} at {:.3}s",
+            key.vk,
+            if key.down { "down" } else { "up" },
+            *elapsed_us as f64 / 1_000_000.0
+        ),
+        None => "nothing".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pipeline::Frame,
+        recorder::{KeyRecorder, Recorder, WindowRecording},
+    };
+
+    const TITLE: &str = "World of Warcraft";
+
+    // A recording in a file of its own, removed when the test is done with it
+    struct TempRecording {
+        path: String,
+        recorder: &'static Recorder,
+    }
+
+    impl TempRecording {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("multibox_{}_{}.jsonl", name, std::process::id()))
+                .to_string_lossy()
+                .into_owned();
+            let recorder = Recorder::create(&path).unwrap();
+            Self {
+                path,
+                recorder: Box::leak(Box::new(recorder)),
+            }
+        }
+
+        fn contents(&self) -> String {
+            std::fs::read_to_string(&self.path).unwrap()
+        }
+    }
+
+    impl Drop for TempRecording {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    // Carries out every command and records that it did, like a window's loop
+    struct Executes(WindowRecording);
+
+    impl ControlHandler for Executes {
+        fn handle(&self, _command: ControlCommand, _keys_enabled: &mut bool) -> Feedback {
+            self.0.record(RecordedEvent::Command {
+                feedback: Feedback::CommandExecuted,
+            });
+            Feedback::CommandExecuted
+        }
+    }
+
+    fn frame(blue: u8, red: u8, bitmask: u8) -> Frame {
+        Frame {
+            pixels: Some((((blue as u32) << 16) | red as u32, bitmask as u32)),
+            broadcast_enabled: true,
+            paused: false,
+            foreground: false,
+        }
+    }
+
+    // Run each window's frames through a pipeline the way its loop does, recording as it
+    // goes. The windows take turns frame by frame, like the loops running side by side.
+    fn record_windows(recorder: &'static Recorder, streams: &[Vec<Frame>]) {
+        let windows: Vec<_> = streams
+            .iter()
+            .map(|_| {
+                let recording = recorder.start_window(TITLE, InjectionMethod::Record);
+                let injector = KeyStateTracker::new(KeyRecorder::wrap(
+                    Box::new(ReplayInjector::default()),
+                    Some(recording),
+                ));
+                (recording, injector, Executes(recording))
+            })
+            .collect();
+        let mut pipelines: Vec<_> = streams
+            .iter()
+            .map(|_| WindowPipeline::new(TITLE, false))
+            .collect();
+        let longest = streams.iter().map(Vec::len).max().unwrap_or(0);
+        for i in 0..longest {
+            for ((stream, (recording, injector, control)), pipeline) in
+                streams.iter().zip(&windows).zip(&mut pipelines)
+            {
+                if let Some(frame) = stream.get(i) {
+                    recording.record(RecordedEvent::Frame(*frame));
+                    pipeline.process(frame, injector, control);
+                }
+            }
+        }
+        for (_, injector, _) in &windows {
+            injector.release_all();
+        }
+    }
+
+    // Forward held, a command, a macro press, then the addon is lost
+    fn short_stream() -> Vec<Frame> {
+        let mut frames = vec![frame(0, 0, 0), frame(0, 0, 4), frame(200, 0, 4)];
+        frames.extend((0..40).map(|_| frame(0, 3, 4)));
+        frames.push(Frame {
+            pixels: None,
+            ..frame(0, 0, 0)
+        });
+        frames
+    }
+
+    #[test]
+    fn replays_a_recording_without_diverging() {
+        let recording = TempRecording::new("replays");
+        record_windows(recording.recorder, &[short_stream()]);
+        let contents = recording.contents();
+        assert!(contents.contains(r#""event":"command""#));
+        assert!(replay_recording(&contents, &recording.path));
+    }
+
+    #[test]
+    fn broadcast_keys_are_recorded_but_not_replayed() {
+        let recording = TempRecording::new("broadcast");
+        let window = recording
+            .recorder
+            .start_window(TITLE, InjectionMethod::PostMessage);
+        window.record(RecordedEvent::Frame(frame(0, 0, 0)));
+        window.record(RecordedEvent::Broadcast {
+            vk: 0x31,
+            down: true,
+        });
+        window.record(RecordedEvent::Broadcast {
+            vk: 0x31,
+            down: false,
+        });
+        // What the pipeline sends for the frame, a newly seen addon is told the tool runs
+        for vk in [Feedback::BroadcastOn.key(), Feedback::Alive.key()] {
+            window.record(RecordedEvent::Key { vk, down: true });
+            window.record(RecordedEvent::Key { vk, down: false });
+        }
+        assert!(replay_recording(&recording.contents(), &recording.path));
+    }
+
+    #[test]
+    fn windows_with_the_same_title_are_replayed_separately() {
+        let recording = TempRecording::new("same_title");
+        // A second client with the same title that moves differently
+        let other: Vec<Frame> = (0..short_stream().len())
+            .map(|i| frame(0, 0, if i % 2 == 0 { 8 } else { 0 }))
+            .collect();
+        record_windows(recording.recorder, &[short_stream(), other]);
+        assert!(replay_recording(&recording.contents(), &recording.path));
+    }
+
+    #[test]
+    fn reports_a_divergence() {
+        let recording = TempRecording::new("diverges");
+        record_windows(recording.recorder, &[short_stream()]);
+        // Drop the last key event the window sent
+        let contents = recording.contents();
+        let mut lines: Vec<&str> = contents.lines().collect();
+        let last_key = lines
+            .iter()
+            .rposition(|line| line.contains(r#""event":"key""#))
+            .unwrap();
+        lines.remove(last_key);
+        assert!(!replay_recording(&lines.join("\n"), &recording.path));
+    }
+}
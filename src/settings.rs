@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    hotkeys::HotkeyConfig, launcher::LaunchSettings, leader::LeaderSettings, pin::PinSettings,
+};
+
+// Global options that are not tied to a slot, loaded from settings.json.
+// Every field has a default so the file and any of its keys may be omitted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Put the original window titles back on shutdown instead of leaving "OMB N"
+    pub restore_titles: bool,
+    // Title given to each game window, "OMB {number}" when missing
+    pub title_template: Option<String>,
+    // Global hotkeys, handled in the keyboard hook and never passed on to the focused window
+    pub hotkeys: Vec<HotkeyConfig>,
+    // Leader election and follow keys, off when missing
+    pub leader: Option<LeaderSettings>,
+    // Record every frame and key of every window to this file, for `replay`
+    pub record_file: Option<String>,
+    // Open the status dashboard at startup
+    pub dashboard: bool,
+    // Keep windows in their slots, off when missing
+    pub pin: Option<PinSettings>,
+    // Start clients from the slots' "launch" entries, off when missing
+    pub launch: Option<LaunchSettings>,
+}
+
+// Load settings from JSON file, falling back to defaults when it is missing or invalid
+pub fn load_settings() -> Settings {
+    match std::fs::read_to_string("settings.json") {
+        Ok(contents) => match serde_json::from_str::<Settings>(&contents) {
+            Ok(settings) => settings,
+            Err(e) => {
+                println!("Failed to parse settings, using defaults: {}", e);
+                Settings::default()
+            }
+        },
+        Err(_) => Settings::default(),
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    collections::BTreeSet,
+    f32::consts::{PI, TAU},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    injector::KeyInjector,
+    movement::{
+        Clock, MovementController, SimulatedClock, BACKPEDAL_SPEED, DISTANCE_TOLERANCE,
+        HEADING_TOLERANCE, MOVEMENT_KEYS, MOVE_BACKWARD, MOVE_FORWARD, ROTATE_LEFT, ROTATE_RIGHT,
+        RUN_SPEED, STRAFE_LEFT, STRAFE_RIGHT, TURN_RATE,
+    },
+    protocol::{MovementPixel, DISTANCE_STEP, HEADING_STEP, HEARTBEAT_BIT},
+};
+
+// Offline model of a character steered by the movement pixel, to check the movement
+// controller without a game. Positions are in yards on the map, x to the east and y to
+// the south like GetPlayerMapPosition. Facing is in radians, 0 = north, growing to the left.
+
+// Fudge factors of the bitmask in Movement.lua
+const ROTATION_FUDGE_FACTOR: f32 = 0.2; // Radians
+const MOVEMENT_FUDGE_FACTOR: f32 = 0.5; // Yards
+
+// How often the game draws a frame, and so how often the pixel changes
+pub const DEFAULT_TICK: Duration = Duration::from_millis(16);
+
+// How often the addon flips the heartbeat bit
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(250);
+
+// Keyboard of the simulated game, remembering which keys are down
+#[derive(Default)]
+pub struct SimulatedKeys {
+    down: Mutex<BTreeSet<u16>>,
+}
+
+impl SimulatedKeys {
+    pub fn is_down(&self, vk: u16) -> bool {
+        self.down.lock().unwrap().contains(&vk)
+    }
+
+    // Movement bits whose keys are down
+    pub fn bits(&self) -> u8 {
+        MOVEMENT_KEYS
+            .iter()
+            .filter(|&&(_, vk)| self.is_down(vk))
+            .fold(0, |bits, &(bit, _)| bits | bit)
+    }
+}
+
+impl KeyInjector for SimulatedKeys {
+    fn key_down(&self, vk: u16) {
+        self.down.lock().unwrap().insert(vk);
+    }
+
+    fn key_up(&self, vk: u16) {
+        self.down.lock().unwrap().remove(&vk);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Character {
+    pub x: f32,
+    pub y: f32,
+    pub facing: f32,
+}
+
+impl Character {
+    // Move for a while with the given movement bits held, at the speeds of the
+    // controller's kinematic model
+    pub fn step(&mut self, bits: u8, dt: Duration) {
+        let dt = dt.as_secs_f32();
+        if bits & ROTATE_LEFT != 0 {
+            self.facing += TURN_RATE * dt;
+        }
+        if bits & ROTATE_RIGHT != 0 {
+            self.facing -= TURN_RATE * dt;
+        }
+        self.facing = self.facing.rem_euclid(TAU);
+
+        let mut forward = 0.0;
+        if bits & MOVE_FORWARD != 0 {
+            forward += RUN_SPEED;
+        }
+        if bits & MOVE_BACKWARD != 0 {
+            forward -= BACKPEDAL_SPEED;
+        }
+        let mut right = 0.0;
+        if bits & STRAFE_RIGHT != 0 {
+            right += RUN_SPEED;
+        }
+        if bits & STRAFE_LEFT != 0 {
+            right -= RUN_SPEED;
+        }
+
+        let (sin, cos) = self.facing.sin_cos();
+        // Forward is (-sin, -cos) and right is (cos, -sin) on the map
+        self.x += (-sin * forward + cos * right) * dt;
+        self.y += (-cos * forward - sin * right) * dt;
+    }
+
+    pub fn distance_to(&self, x: f32, y: f32) -> f32 {
+        (x - self.x).hypot(y - self.y)
+    }
+}
+
+// What the addon wants the character to do, from getMovementRotationBitmask
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MovementRequest {
+    pub bitmask: u8,
+    pub heading_error: Option<f32>,
+    pub distance: Option<f32>,
+    pub done: bool,
+}
+
+impl MovementRequest {
+    // The movement pixel color the addon draws for this request, 0x00BBGGRR.
+    // A reached target is drawn as 0, apart from the heartbeat.
+    pub fn color(&self, heartbeat: bool) -> u32 {
+        let heartbeat = if heartbeat { HEARTBEAT_BIT as u32 } else { 0 };
+        if self.done {
+            return heartbeat;
+        }
+        let heading = encode_signed(self.heading_error, HEADING_STEP) as u32;
+        let distance = encode_signed(self.distance, DISTANCE_STEP) as u32;
+        self.bitmask as u32 | heartbeat | heading << 8 | distance << 16
+    }
+}
+
+// encodeSigned in Multibox.lua
+fn encode_signed(value: Option<f32>, step: f32) -> u8 {
+    match value {
+        None => 0,
+        Some(value) => ((value / step + 0.5).floor().clamp(-127.0, 127.0) + 128.0) as u8,
+    }
+}
+
+// Port of MultiboxMovement:getMovementRotationBitmask, with the target already in yards
+pub fn movement_request(
+    character: &Character,
+    target_rotation: Option<f32>,
+    target_position: Option<(f32, f32)>,
+) -> MovementRequest {
+    let mut request = MovementRequest {
+        done: true,
+        ..Default::default()
+    };
+
+    if let Some(target_rotation) = target_rotation {
+        let mut difference = target_rotation - character.facing;
+        if difference > PI {
+            difference -= TAU;
+        } else if difference < -PI {
+            difference += TAU;
+        }
+
+        if difference < -ROTATION_FUDGE_FACTOR {
+            request.bitmask |= ROTATE_RIGHT;
+        } else if difference > ROTATION_FUDGE_FACTOR {
+            request.bitmask |= ROTATE_LEFT;
+        }
+
+        request.heading_error = Some(difference);
+        if difference.abs() > HEADING_TOLERANCE {
+            request.done = false;
+        }
+    }
+
+    if let Some((target_x, target_y)) = target_position {
+        let dx = target_x - character.x;
+        let dy = target_y - character.y;
+        let (sin, cos) = character.facing.sin_cos();
+        let relative_x = dx * cos - dy * sin;
+        let relative_y = -dx * sin - dy * cos;
+
+        if relative_y > MOVEMENT_FUDGE_FACTOR {
+            request.bitmask |= MOVE_FORWARD;
+        } else if relative_y < -MOVEMENT_FUDGE_FACTOR {
+            request.bitmask |= MOVE_BACKWARD;
+        }
+        if relative_x > MOVEMENT_FUDGE_FACTOR {
+            request.bitmask |= STRAFE_RIGHT;
+        } else if relative_x < -MOVEMENT_FUDGE_FACTOR {
+            request.bitmask |= STRAFE_LEFT;
+        }
+
+        request.distance = Some(relative_y);
+        if relative_y.abs() > DISTANCE_TOLERANCE || relative_x.abs() > MOVEMENT_FUDGE_FACTOR {
+            request.done = false;
+        }
+    }
+
+    request
+}
+
+// A character, the addon's target and the controller steering between them
+pub struct Simulation {
+    pub character: Character,
+    pub target_rotation: Option<f32>,
+    pub target_position: Option<(f32, f32)>,
+    pub tick: Duration,
+    started: Instant,
+    clock: SimulatedClock,
+    keys: SimulatedKeys,
+    controller: MovementController<SimulatedClock>,
+}
+
+impl Simulation {
+    pub fn new(
+        character: Character,
+        target_rotation: Option<f32>,
+        target_position: Option<(f32, f32)>,
+    ) -> Self {
+        let clock = SimulatedClock::new();
+        Self {
+            character,
+            target_rotation,
+            target_position,
+            tick: DEFAULT_TICK,
+            started: clock.now(),
+            controller: MovementController::with_clock(clock.clone()),
+            clock,
+            keys: SimulatedKeys::default(),
+        }
+    }
+
+    // One game frame: the addon draws the pixel, the tool reads it and presses keys,
+    // then the character moves with whatever keys are down.
+    // Returns whether the addon considers the target reached.
+    pub fn step(&mut self) -> bool {
+        let request = movement_request(&self.character, self.target_rotation, self.target_position);
+        if request.done {
+            // The addon forgets a reached target
+            self.target_rotation = None;
+            self.target_position = None;
+        }
+        let elapsed = self.clock.now().duration_since(self.started);
+        let heartbeat = (elapsed.as_millis() / HEARTBEAT_INTERVAL.as_millis()) % 2 == 1;
+        let pixel = MovementPixel::decode(request.color(heartbeat));
+        self.controller.update(&self.keys, pixel);
+        self.clock.advance(self.tick);
+        self.character.step(self.keys.bits(), self.tick);
+        request.done
+    }
+
+    // Step until the target is reached and every key is up.
+    // Returns the number of ticks it took, None if it took more than max_ticks.
+    pub fn run(&mut self, max_ticks: usize) -> Option<usize> {
+        (1..=max_ticks).find(|_| self.step() && self.keys.bits() == 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Maximum number of ticks a scenario may take, 10 seconds of frames
+    const MAX_TICKS: usize = 625;
+
+    const fn at(x: f32, y: f32, facing: f32) -> Character {
+        Character { x, y, facing }
+    }
+
+    // Steer the character to the target and check it ends within 1 yard of the position
+    fn assert_reaches(
+        start: Character,
+        target_rotation: Option<f32>,
+        target_position: Option<(f32, f32)>,
+    ) {
+        let mut simulation = Simulation::new(start, target_rotation, target_position);
+        let ticks = simulation.run(MAX_TICKS);
+        let character = simulation.character;
+        let distance = target_position.map_or(0.0, |(x, y)| character.distance_to(x, y));
+        assert!(
+            ticks.is_some(),
+            "not done after {} ticks, {:.2} yards from the target, facing {:.3}",
+            MAX_TICKS,
+            distance,
+            character.facing
+        );
+        assert!(
+            distance <= 1.0,
+            "{:.2} yards from the target, facing {:.3}",
+            distance,
+            character.facing
+        );
+    }
+
+    #[test]
+    fn turn_left_a_quarter() {
+        assert_reaches(at(0.0, 0.0, 0.0), Some(PI / 2.0), None);
+    }
+
+    #[test]
+    fn turn_right_a_little() {
+        assert_reaches(at(0.0, 0.0, 1.0), Some(0.9), None);
+    }
+
+    #[test]
+    fn run_20_yards_ahead() {
+        assert_reaches(at(0.0, 0.0, 0.0), None, Some((0.0, -20.0)));
+    }
+
+    // Farther than the movement pixel can carry, the distance reads the same for a while
+    #[test]
+    fn run_40_yards_ahead() {
+        assert_reaches(at(0.0, 0.0, 0.0), None, Some((0.0, -40.0)));
+    }
+
+    #[test]
+    fn run_60_yards_ahead() {
+        assert_reaches(at(0.0, 0.0, 0.0), None, Some((0.0, -60.0)));
+    }
+
+    #[test]
+    fn back_up_3_yards() {
+        assert_reaches(at(0.0, 0.0, 0.0), None, Some((0.0, 3.0)));
+    }
+
+    #[test]
+    fn follower_turns_around_and_runs_to_the_leader() {
+        assert_reaches(at(10.0, 10.0, 0.3), Some(3.0 * PI / 4.0), Some((0.0, 20.0)));
+    }
+}
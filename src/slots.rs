@@ -0,0 +1,148 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+const SLOT_ASSIGNMENTS_FILE: &str = "slot_assignments.json";
+
+// Which layout slot each OMB window sits in, i.e. which entry of window_config.json
+// places it. A window keeps its OMB number (and title) for as long as it lives, so
+// swapping two windows swaps their layout slots instead.
+// Windows that are not listed sit in the slot matching their own number.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlotAssignments {
+    // OMB number -> layout slot number, both starting at 1
+    slots: BTreeMap<usize, usize>,
+}
+
+impl SlotAssignments {
+    // The layout slot an OMB window is assigned to
+    pub fn slot_of(&self, omb_number: usize) -> usize {
+        self.slots.get(&omb_number).copied().unwrap_or(omb_number)
+    }
+
+    // The OMB number of the window assigned to a layout slot
+    pub fn omb_in(&self, slot: usize) -> usize {
+        self.slots
+            .iter()
+            .find(|&(_, &assigned)| assigned == slot)
+            .map(|(&omb_number, _)| omb_number)
+            .unwrap_or(slot)
+    }
+
+    // Exchange the layout slots of two OMB windows
+    pub fn swap(&mut self, omb_a: usize, omb_b: usize) {
+        let (slot_a, slot_b) = (self.slot_of(omb_a), self.slot_of(omb_b));
+        self.assign(omb_a, slot_b);
+        self.assign(omb_b, slot_a);
+    }
+
+    fn assign(&mut self, omb_number: usize, slot: usize) {
+        if omb_number == slot {
+            self.slots.remove(&omb_number);
+        } else {
+            self.slots.insert(omb_number, slot);
+        }
+    }
+
+    // Every slot must hold exactly one window. Swaps keep it that way,
+    // but the file may have been edited by hand.
+    fn is_valid(&self) -> bool {
+        let numbers: BTreeSet<usize> = self.slots.keys().copied().collect();
+        let slots: BTreeSet<usize> = self.slots.values().copied().collect();
+        slots.len() == self.slots.len() && numbers == slots && !numbers.contains(&0)
+    }
+
+    pub fn save(&self) {
+        match serde_json::to_string_pretty(self) {
+            Ok(contents) => {
+                if let Err(e) = std::fs::write(SLOT_ASSIGNMENTS_FILE, contents) {
+                    println!("Failed to save slot assignments: {}", e);
+                }
+            }
+            Err(e) => println!("Failed to serialize slot assignments: {}", e),
+        }
+    }
+}
+
+// Load the slot assignments saved by earlier swaps, starting with every window in its
+// own slot when there are none
+pub fn load_slot_assignments() -> SlotAssignments {
+    match std::fs::read_to_string(SLOT_ASSIGNMENTS_FILE) {
+        Ok(contents) => match serde_json::from_str::<SlotAssignments>(&contents) {
+            Ok(assignments) if assignments.is_valid() => assignments,
+            Ok(_) => {
+                println!("Slot assignments put two windows in one slot, ignoring them");
+                SlotAssignments::default()
+            }
+            Err(e) => {
+                println!("Failed to parse slot assignments, ignoring them: {}", e);
+                SlotAssignments::default()
+            }
+        },
+        Err(_) => SlotAssignments::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assignments(json: &str) -> SlotAssignments {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn windows_start_in_their_own_slots() {
+        let assignments = SlotAssignments::default();
+        assert_eq!(assignments.slot_of(3), 3);
+        assert_eq!(assignments.omb_in(3), 3);
+    }
+
+    #[test]
+    fn swapping_into_the_main_slot() {
+        let mut assignments = SlotAssignments::default();
+        assignments.swap(3, assignments.omb_in(1));
+        assert_eq!(assignments.omb_in(1), 3);
+        assert_eq!(assignments.slot_of(1), 3);
+        assert!(assignments.is_valid());
+
+        // The next window swapped into the main slot sends OMB 3 back where OMB 2 was
+        assignments.swap(2, assignments.omb_in(1));
+        assert_eq!(assignments.omb_in(1), 2);
+        assert_eq!(assignments.slot_of(3), 2);
+        assert_eq!(assignments.slot_of(1), 3);
+        assert!(assignments.is_valid());
+    }
+
+    #[test]
+    fn swapping_back_forgets_the_assignment() {
+        let mut assignments = SlotAssignments::default();
+        assignments.swap(1, 4);
+        assignments.swap(4, 1);
+        assert_eq!(assignments, SlotAssignments::default());
+    }
+
+    #[test]
+    fn swapping_a_window_with_itself_changes_nothing() {
+        let mut assignments = SlotAssignments::default();
+        assignments.swap(2, 2);
+        assert_eq!(assignments, SlotAssignments::default());
+    }
+
+    #[test]
+    fn rejects_invalid_assignments() {
+        let cases = [
+            (r#"{ "slots": {} }"#, true),
+            (r#"{ "slots": { "1": 3, "3": 1 } }"#, true),
+            (r#"{ "slots": { "1": 2, "2": 3, "3": 1 } }"#, true),
+            // Two windows in slot 2
+            (r#"{ "slots": { "1": 2, "3": 2 } }"#, false),
+            // Slot 1 is left empty while OMB 2 has two slots
+            (r#"{ "slots": { "1": 2 } }"#, false),
+            (r#"{ "slots": { "0": 1, "1": 0 } }"#, false),
+        ];
+        for (json, valid) in cases {
+            assert_eq!(assignments(json).is_valid(), valid, "{}", json);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::health::WindowStatus;
+
+// Span macro presses are counted over for the commands per minute
+const COMMAND_RATE_WINDOW: Duration = Duration::from_secs(60);
+
+// What the dashboard shows about one window. Each window's thread keeps its own entry
+// up to date.
+#[derive(Debug, Clone)]
+pub struct WindowEntry {
+    pub omb_number: usize,
+    // Name of the character played in the window, from window_config.json
+    pub character: Option<String>,
+    pub status: WindowStatus,
+    // The last control command the addon showed and the feedback it got
+    pub last_command: Option<String>,
+    // How long the last capture of the window's pixels took
+    pub capture_latency: Duration,
+    macro_presses: VecDeque<Instant>,
+}
+
+impl WindowEntry {
+    pub fn new(title: &str, omb_number: usize, character: Option<String>) -> Self {
+        Self {
+            omb_number,
+            character,
+            status: WindowStatus::new(title),
+            last_command: None,
+            capture_latency: Duration::ZERO,
+            macro_presses: VecDeque::new(),
+        }
+    }
+
+    pub fn record_macro_press(&mut self, now: Instant) {
+        self.macro_presses.push_back(now);
+        while self
+            .macro_presses
+            .front()
+            .is_some_and(|&pressed| now.duration_since(pressed) > COMMAND_RATE_WINDOW)
+        {
+            self.macro_presses.pop_front();
+        }
+    }
+
+    // Macro keys pressed during the last minute
+    pub fn commands_per_minute(&self, now: Instant) -> usize {
+        self.macro_presses
+            .iter()
+            .filter(|&&pressed| now.duration_since(pressed) <= COMMAND_RATE_WINDOW)
+            .count()
+    }
+}
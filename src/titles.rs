@@ -0,0 +1,201 @@
+use crate::health::WindowState;
+
+// The part of a window title the tool finds its windows by, followed by the OMB number
+const SLOT_TAG: &str = "OMB ";
+
+// Title used when settings.json has no title_template
+pub const DEFAULT_TITLE_TEMPLATE: &str = "OMB {number}";
+
+// Characters trimmed off the ends of a title, left over from placeholders that were empty
+const SEPARATORS: &[char] = &[' ', '-', '|', ':', ','];
+
+// What a window title is made of
+pub struct TitleInfo<'a> {
+    pub omb_number: usize,
+    pub character: Option<&'a str>,
+    pub state: WindowState,
+    pub paused: bool,
+}
+
+pub fn slot_tag(omb_number: usize) -> String {
+    format!("{}{}", SLOT_TAG, omb_number)
+}
+
+// Find the OMB number in a title, wherever the template put the tag, e.g. 3 in
+// "Healbot (OMB 3) [paused]". The first tag counts, the title may end in a nul.
+pub fn parse_slot_tag(title: &str) -> Option<usize> {
+    let mut rest = title;
+    while let Some(start) = rest.find(SLOT_TAG) {
+        // "ZOMB 3" is not a tag
+        let standalone = rest[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric());
+        let after = &rest[start + SLOT_TAG.len()..];
+        let digits = after
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(after.len());
+        if standalone && digits > 0 {
+            // OMB numbers start at 1, and a number too big to parse is no OMB number either
+            if let Ok(number @ 1..) = after[..digits].parse() {
+                return Some(number);
+            }
+        }
+        rest = after;
+    }
+    None
+}
+
+// Fill in a title template. {number} is the OMB number, {character} the slot's character
+// and {status} what is wrong with the window in brackets, e.g. "[paused]". Titles that
+// lose the slot tag get it put in front, so the tool still recognizes its windows.
+pub fn render_title(template: &str, info: &TitleInfo) -> String {
+    let mut status = Vec::new();
+    if info.paused {
+        status.push("paused");
+    }
+    match info.state {
+        WindowState::CaptureFailed => status.push("capture failed"),
+        WindowState::AddonMissing => status.push("addon missing"),
+        WindowState::Stale => status.push("stale"),
+        WindowState::Discovered | WindowState::Active | WindowState::Closed => {}
+    }
+    let status = if status.is_empty() {
+        String::new()
+    } else {
+        format!("[{}]", status.join(", "))
+    };
+
+    let rendered = template
+        .replace("{number}", &info.omb_number.to_string())
+        .replace("{character}", info.character.unwrap_or(""))
+        .replace("{status}", &status);
+    let title = rendered
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_matches(SEPARATORS)
+        .to_string();
+
+    let tag = slot_tag(info.omb_number);
+    if parse_slot_tag(&title) == Some(info.omb_number) {
+        title
+    } else if title.is_empty() {
+        tag
+    } else {
+        format!("{} - {}", tag, title)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(character: Option<&str>, state: WindowState, paused: bool) -> TitleInfo<'_> {
+        TitleInfo {
+            omb_number: 2,
+            character,
+            state,
+            paused,
+        }
+    }
+
+    #[test]
+    fn parses_slot_tags() {
+        let cases = [
+            ("OMB 3", Some(3)),
+            ("OMB 12", Some(12)),
+            ("Healbot (OMB 3) [paused]", Some(3)),
+            ("OMB 3 - Healbot", Some(3)),
+            ("Healbot - OMB 4", Some(4)),
+            ("OMB 5\0", Some(5)),
+            // The first tag counts
+            ("OMB 1 OMB 2", Some(1)),
+            // Embedded in a word
+            ("ZOMB 3", None),
+            ("ZOMB 3 OMB 4", Some(4)),
+            ("xOMB 3", None),
+            // No number after the tag
+            ("OMB", None),
+            ("OMB x", None),
+            ("OMB -3", None),
+            ("OMB  3", None),
+            ("World of Warcraft", None),
+            ("", None),
+            // Out of range
+            ("OMB 0", None),
+            ("OMB 0 OMB 7", Some(7)),
+            ("OMB 99999999999999999999999", None),
+        ];
+        for (title, expected) in cases {
+            assert_eq!(parse_slot_tag(title), expected, "{:?}", title);
+        }
+    }
+
+    #[test]
+    fn renders_templates() {
+        let cases = [
+            (
+                "OMB {number}",
+                info(None, WindowState::Active, false),
+                "OMB 2",
+            ),
+            (
+                "{character} - OMB {number} {status}",
+                info(Some("Healbot"), WindowState::Stale, true),
+                "Healbot - OMB 2 [paused, stale]",
+            ),
+            (
+                "OMB {number} | {character} {status}",
+                info(None, WindowState::AddonMissing, false),
+                "OMB 2 | [addon missing]",
+            ),
+            (
+                "OMB {number} {status}",
+                info(None, WindowState::CaptureFailed, false),
+                "OMB 2 [capture failed]",
+            ),
+        ];
+        for (template, info, expected) in cases {
+            assert_eq!(render_title(template, &info), expected, "{:?}", template);
+        }
+    }
+
+    #[test]
+    fn trims_separators_left_by_empty_placeholders() {
+        let cases = [
+            ("{character} - OMB {number}", "OMB 2"),
+            ("OMB {number} - {character}", "OMB 2"),
+            ("OMB {number}: {character}, {status}", "OMB 2"),
+            ("{status} | OMB {number} | {character}", "OMB 2"),
+            ("  OMB   {number}  ", "OMB 2"),
+        ];
+        for (template, expected) in cases {
+            let info = info(None, WindowState::Active, false);
+            assert_eq!(render_title(template, &info), expected, "{:?}", template);
+        }
+    }
+
+    #[test]
+    fn puts_the_tag_back_when_the_template_drops_it() {
+        let cases = [
+            ("{character}", Some("Healbot"), "OMB 2 - Healbot"),
+            ("{character}", None, "OMB 2"),
+            ("", None, "OMB 2"),
+            ("World of Warcraft", None, "OMB 2 - World of Warcraft"),
+            // A tag for another window doesn't count
+            (
+                "OMB 1 {character}",
+                Some("Healbot"),
+                "OMB 2 - OMB 1 Healbot",
+            ),
+            ("ZOMB {number}", None, "OMB 2 - ZOMB 2"),
+        ];
+        for (template, character, expected) in cases {
+            let info = info(character, WindowState::Active, false);
+            let title = render_title(template, &info);
+            assert_eq!(title, expected, "{:?}", template);
+            assert_eq!(parse_slot_tag(&title), Some(2), "{:?}", title);
+        }
+    }
+}
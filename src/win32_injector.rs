@@ -0,0 +1,213 @@
+use std::{
+    sync::{LazyLock, Mutex},
+    thread::sleep,
+    time::Duration,
+};
+
+use windows::Win32::{
+    Foundation::{LPARAM, WPARAM},
+    System::Threading::{AttachThreadInput, GetCurrentThreadId},
+    UI::{
+        Input::KeyboardAndMouse::{
+            GetKeyboardState, MapVirtualKeyW, SendInput, SetKeyboardState, INPUT, INPUT_0,
+            INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS, KEYEVENTF_KEYUP, MAPVK_VK_TO_VSC,
+            VIRTUAL_KEY, VK_CONTROL, VK_LCONTROL, VK_LMENU, VK_LSHIFT, VK_MENU, VK_RCONTROL,
+            VK_RMENU, VK_RSHIFT, VK_SHIFT,
+        },
+        WindowsAndMessaging::{
+            GetForegroundWindow, GetWindowThreadProcessId, PostMessageW, SetForegroundWindow,
+            WM_KEYDOWN, WM_KEYUP,
+        },
+    },
+};
+
+use crate::{
+    injector::{InjectionMethod, KeyInjector, RecordingInjector},
+    HwndWrapper,
+};
+
+// Win32 implementations of KeyInjector. Kept apart from the trait so the key logic builds
+// without Win32 for replays.
+
+// Build the injector for a window using the configured strategy
+pub fn create_injector(
+    method: InjectionMethod,
+    hwnd: HwndWrapper,
+    label: &str,
+) -> Box<dyn KeyInjector> {
+    match method {
+        InjectionMethod::PostMessage => Box::new(PostMessageInjector { hwnd }),
+        InjectionMethod::SendInput => Box::new(SendInputInjector { hwnd }),
+        InjectionMethod::KeyboardState => Box::new(KeyboardStateInjector { hwnd }),
+        InjectionMethod::Record => Box::new(RecordingInjector::new(label)),
+    }
+}
+
+fn post_key(hwnd: HwndWrapper, message: u32, vk: u16) {
+    unsafe {
+        let _ = PostMessageW(Some(hwnd.0), message, WPARAM(vk.into()), LPARAM(0));
+    }
+}
+
+pub struct PostMessageInjector {
+    hwnd: HwndWrapper,
+}
+
+impl KeyInjector for PostMessageInjector {
+    fn key_down(&self, vk: u16) {
+        post_key(self.hwnd, WM_KEYDOWN, vk);
+    }
+
+    fn key_up(&self, vk: u16) {
+        post_key(self.hwnd, WM_KEYUP, vk);
+    }
+}
+
+// Only one window may own the foreground at a time, so focus-and-send is serialized
+// across all window threads.
+static FOCUS_LOCK: LazyLock<Mutex<()>> = LazyLock::new(|| Mutex::new(()));
+
+pub struct SendInputInjector {
+    hwnd: HwndWrapper,
+}
+
+impl SendInputInjector {
+    fn keyboard_input(vk: u16, up: bool) -> INPUT {
+        let scan = unsafe { MapVirtualKeyW(vk.into(), MAPVK_VK_TO_VSC) } as u16;
+        INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: VIRTUAL_KEY(vk),
+                    wScan: scan,
+                    dwFlags: if up {
+                        KEYEVENTF_KEYUP
+                    } else {
+                        KEYBD_EVENT_FLAGS(0)
+                    },
+                    time: 0,
+                    dwExtraInfo: 0,
+                },
+            },
+        }
+    }
+
+    // Focus the target window and send the whole batch in one SendInput call,
+    // so it can't be interleaved with other input.
+    fn send(&self, inputs: &[INPUT]) {
+        let _guard = FOCUS_LOCK.lock().unwrap();
+        unsafe {
+            if GetForegroundWindow() != self.hwnd.0 {
+                let _ = SetForegroundWindow(self.hwnd.0);
+                if GetForegroundWindow() != self.hwnd.0 {
+                    // Never type into whatever window happens to be focused instead
+                    println!("Failed to focus window {:?}, dropping input", self.hwnd.0);
+                    return;
+                }
+            }
+            SendInput(inputs, std::mem::size_of::<INPUT>() as i32);
+        }
+    }
+}
+
+impl KeyInjector for SendInputInjector {
+    fn key_down(&self, vk: u16) {
+        self.send(&[Self::keyboard_input(vk, false)]);
+    }
+
+    fn key_up(&self, vk: u16) {
+        self.send(&[Self::keyboard_input(vk, true)]);
+    }
+
+    fn tap(&self, vk: u16) {
+        self.send(&[
+            Self::keyboard_input(vk, false),
+            Self::keyboard_input(vk, true),
+        ]);
+    }
+
+    fn chord(&self, modifier: Option<u16>, vk: u16) {
+        let mut inputs = Vec::with_capacity(4);
+        if let Some(modifier) = modifier {
+            inputs.push(Self::keyboard_input(modifier, false));
+        }
+        inputs.push(Self::keyboard_input(vk, false));
+        inputs.push(Self::keyboard_input(vk, true));
+        if let Some(modifier) = modifier {
+            inputs.push(Self::keyboard_input(modifier, true));
+        }
+        self.send(&inputs);
+    }
+}
+
+// Time the client gets to process a posted key before the emulated modifier is released
+const KEY_STATE_SETTLE: Duration = Duration::from_millis(15);
+
+pub struct KeyboardStateInjector {
+    hwnd: HwndWrapper,
+}
+
+impl KeyboardStateInjector {
+    // The generic modifier key that must be set alongside a left/right specific one
+    fn generic_modifier(vk: u16) -> Option<u16> {
+        match VIRTUAL_KEY(vk) {
+            VK_LCONTROL | VK_RCONTROL => Some(VK_CONTROL.0),
+            VK_LSHIFT | VK_RSHIFT => Some(VK_SHIFT.0),
+            VK_LMENU | VK_RMENU => Some(VK_MENU.0),
+            _ => None,
+        }
+    }
+
+    // Set or clear a key in the keyboard state of the window's input thread
+    fn set_key_state(&self, vk: u16, down: bool) {
+        unsafe {
+            let target_thread = GetWindowThreadProcessId(self.hwnd.0, None);
+            if target_thread == 0 {
+                return;
+            }
+            let current_thread = GetCurrentThreadId();
+            let attached = AttachThreadInput(current_thread, target_thread, true).as_bool();
+
+            let mut state = [0u8; 256];
+            if GetKeyboardState(&mut state).is_ok() {
+                let keys = std::iter::once(vk).chain(Self::generic_modifier(vk));
+                for key in keys {
+                    if down {
+                        state[key as usize] |= 0x80;
+                    } else {
+                        state[key as usize] &= !0x80;
+                    }
+                }
+                let _ = SetKeyboardState(&state);
+            }
+
+            if attached {
+                let _ = AttachThreadInput(current_thread, target_thread, false);
+            }
+        }
+    }
+}
+
+impl KeyInjector for KeyboardStateInjector {
+    fn key_down(&self, vk: u16) {
+        self.set_key_state(vk, true);
+        post_key(self.hwnd, WM_KEYDOWN, vk);
+    }
+
+    fn key_up(&self, vk: u16) {
+        post_key(self.hwnd, WM_KEYUP, vk);
+        self.set_key_state(vk, false);
+    }
+
+    fn chord(&self, modifier: Option<u16>, vk: u16) {
+        if let Some(modifier) = modifier {
+            self.key_down(modifier);
+        }
+        self.tap(vk);
+        if let Some(modifier) = modifier {
+            // Posted messages are processed later, keep the modifier down until they are
+            sleep(KEY_STATE_SETTLE);
+            self.key_up(modifier);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use windows::{
+    core::BOOL,
+    Win32::{
+        Foundation::{LPARAM, RECT},
+        Graphics::Gdi::{EnumDisplayMonitors, GetMonitorInfoW, HDC, HMONITOR, MONITORINFOEXW},
+        UI::{
+            HiDpi::{
+                GetDpiForMonitor, SetProcessDpiAwarenessContext,
+                DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, MDT_EFFECTIVE_DPI,
+            },
+            WindowsAndMessaging::MONITORINFOF_PRIMARY,
+        },
+    },
+};
+
+use crate::{
+    layout::Rect,
+    monitors::{sort_monitors, Monitor, MonitorSource},
+};
+
+// DPI of a monitor at 100% scaling
+const DEFAULT_DPI: f32 = 96.0;
+
+// The monitors connected right now
+pub struct Win32Monitors;
+
+impl MonitorSource for Win32Monitors {
+    fn monitors(&self) -> Vec<Monitor> {
+        let mut monitors: Vec<Monitor> = Vec::new();
+        unsafe {
+            let _ = EnumDisplayMonitors(
+                None,
+                None,
+                Some(enum_monitor_callback),
+                LPARAM(&mut monitors as *mut Vec<Monitor> as isize),
+            );
+        }
+        sort_monitors(&mut monitors);
+        monitors
+    }
+}
+
+// Work in physical pixels on every monitor. Without this Windows scales the coordinates of
+// a process it thinks can't handle DPI, and windows end up in the wrong place on scaled
+// monitors.
+pub fn enable_dpi_awareness() {
+    unsafe {
+        if let Err(e) = SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) {
+            println!("Failed to enable per monitor DPI awareness: {}", e);
+        }
+    }
+}
+
+unsafe extern "system" fn enum_monitor_callback(
+    monitor: HMONITOR,
+    _: HDC,
+    _: *mut RECT,
+    data: LPARAM,
+) -> BOOL {
+    let monitors = &mut *(data.0 as *mut Vec<Monitor>);
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+    if GetMonitorInfoW(monitor, &mut info.monitorInfo).as_bool() {
+        let rect = info.monitorInfo.rcMonitor;
+        let name_len = info
+            .szDevice
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(info.szDevice.len());
+        let (mut dpi_x, mut dpi_y) = (0, 0);
+        let scale = match GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) {
+            Ok(()) => dpi_x as f32 / DEFAULT_DPI,
+            Err(_) => 1.0,
+        };
+        monitors.push(Monitor {
+            name: String::from_utf16_lossy(&info.szDevice[..name_len]),
+            bounds: Rect {
+                x: rect.left,
+                y: rect.top,
+                width: rect.right - rect.left,
+                height: rect.bottom - rect.top,
+            },
+            primary: info.monitorInfo.dwFlags & MONITORINFOF_PRIMARY != 0,
+            scale,
+        });
+    }
+    BOOL::from(true)
+}
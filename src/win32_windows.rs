@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use windows::{
+    core::BOOL,
+    Win32::{
+        Foundation::{CloseHandle, HANDLE, HWND, LPARAM, WAIT_OBJECT_0, WAIT_TIMEOUT},
+        System::Threading::{
+            GetExitCodeProcess, OpenProcess, WaitForSingleObject,
+            PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_SYNCHRONIZE,
+        },
+        UI::WindowsAndMessaging::{
+            EnumWindows, GetClassNameW, GetWindowTextLengthW, GetWindowTextW,
+            GetWindowThreadProcessId,
+        },
+    },
+};
+
+use crate::{
+    discovery::{TopLevelWindow, WindowSource},
+    HwndWrapper,
+};
+
+// The top level windows open right now
+pub struct Win32Windows;
+
+impl WindowSource for Win32Windows {
+    type Window = HwndWrapper;
+
+    fn windows(&self) -> Vec<TopLevelWindow<HwndWrapper>> {
+        // Each enumeration fills its own list, handed to the callback through the LPARAM
+        let mut windows: Vec<TopLevelWindow<HwndWrapper>> = Vec::new();
+        unsafe {
+            let _ = EnumWindows(
+                Some(enum_window_callback),
+                LPARAM(&mut windows as *mut Vec<TopLevelWindow<HwndWrapper>> as isize),
+            );
+        }
+        windows
+    }
+}
+
+// Id of the process a window belongs to
+pub fn window_process_id(hwnd: HWND) -> u32 {
+    let mut process_id = 0;
+    unsafe {
+        GetWindowThreadProcessId(hwnd, Some(&mut process_id));
+    }
+    process_id
+}
+
+// How a client's process ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessExit {
+    Exited(u32),
+    // Still running when the wait timed out
+    Running,
+    // Its handle couldn't be opened or waited on, so nothing is known about it
+    Unknown,
+}
+
+// A handle to the process of a game window. Opened when the window is registered and held
+// until it is closed, so the exit code can be read after the process is gone: opening it
+// by process id then fails, and the launcher lets go of its own handles once they exited.
+pub struct ProcessHandle {
+    process_id: u32,
+    handle: HANDLE,
+}
+
+// Only waited on and queried, which any thread may do
+unsafe impl Send for ProcessHandle {}
+
+impl ProcessHandle {
+    pub fn open(process_id: u32) -> Option<Self> {
+        let process = unsafe {
+            OpenProcess(
+                PROCESS_QUERY_LIMITED_INFORMATION | PROCESS_SYNCHRONIZE,
+                false,
+                process_id,
+            )
+        };
+        match process {
+            Ok(handle) => Some(Self { process_id, handle }),
+            Err(e) => {
+                println!("Failed to open process {}: {}", process_id, e);
+                None
+            }
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.process_id
+    }
+
+    // Wait up to the timeout for the process to end
+    pub fn wait_for_exit(&self, timeout: Duration) -> ProcessExit {
+        unsafe {
+            match WaitForSingleObject(self.handle, timeout.as_millis() as u32) {
+                WAIT_OBJECT_0 => {
+                    let mut exit_code = 0;
+                    match GetExitCodeProcess(self.handle, &mut exit_code) {
+                        Ok(()) => ProcessExit::Exited(exit_code),
+                        Err(_) => ProcessExit::Unknown,
+                    }
+                }
+                WAIT_TIMEOUT => ProcessExit::Running,
+                _ => ProcessExit::Unknown,
+            }
+        }
+    }
+}
+
+impl Drop for ProcessHandle {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseHandle(self.handle);
+        }
+    }
+}
+
+unsafe extern "system" fn enum_window_callback(hwnd: HWND, data: LPARAM) -> BOOL {
+    let windows = &mut *(data.0 as *mut Vec<TopLevelWindow<HwndWrapper>>);
+    // Class names are at most 256 characters
+    let mut class_buf = [0u16; 257];
+    let copied = GetClassNameW(hwnd, &mut class_buf);
+    let class = String::from_utf16_lossy(&class_buf[..copied.max(0) as usize]);
+
+    let len = GetWindowTextLengthW(hwnd);
+    let mut title_buf = vec![0u16; (len.max(0) + 1) as usize];
+    let copied = GetWindowTextW(hwnd, &mut title_buf);
+    let title = String::from_utf16_lossy(&title_buf[..copied.max(0) as usize]);
+
+    windows.push(TopLevelWindow {
+        window: HwndWrapper(hwnd),
+        class,
+        title,
+    });
+    BOOL::from(true)
+}
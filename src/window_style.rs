@@ -0,0 +1,103 @@
+use windows::Win32::{
+    Foundation::{COLORREF, HWND},
+    UI::WindowsAndMessaging::{
+        GetWindowLongPtrW, IsWindow, SetLayeredWindowAttributes, SetWindowLongPtrW, SetWindowPos,
+        ShowWindow, GWL_EXSTYLE, GWL_STYLE, HWND_NOTOPMOST, HWND_TOPMOST, LWA_ALPHA,
+        SWP_FRAMECHANGED, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE, SW_HIDE, SW_SHOWNOACTIVATE,
+        WS_CAPTION, WS_EX_APPWINDOW, WS_EX_LAYERED, WS_EX_TOOLWINDOW, WS_EX_TOPMOST,
+        WS_EX_TRANSPARENT, WS_MAXIMIZEBOX, WS_MINIMIZEBOX, WS_SYSMENU, WS_THICKFRAME,
+    },
+};
+
+use crate::{layout::WindowStyle, HwndWrapper, APP};
+
+// Give a window the style of the slot it is placed in. Windows keep the styles they had
+// before the tool first touched them, so moving to a plain slot puts those back.
+pub fn apply_window_style(hwnd: HWND, style: WindowStyle) {
+    let wrapped = HwndWrapper(hwnd);
+    let mut original_styles = APP.original_styles.lock().unwrap();
+    let (base_style, base_ex_style) = match original_styles.get(&wrapped) {
+        Some(&styles) => styles,
+        // Windows the tool never styled are left alone
+        None if style == WindowStyle::default() => return,
+        None => {
+            let styles = unsafe {
+                (
+                    GetWindowLongPtrW(hwnd, GWL_STYLE),
+                    GetWindowLongPtrW(hwnd, GWL_EXSTYLE),
+                )
+            };
+            original_styles.insert(wrapped, styles);
+            styles
+        }
+    };
+    drop(original_styles);
+
+    let mut window_style = base_style;
+    let mut ex_style = base_ex_style;
+    if style.borderless {
+        window_style &=
+            !((WS_CAPTION | WS_THICKFRAME | WS_SYSMENU | WS_MINIMIZEBOX | WS_MAXIMIZEBOX).0
+                as isize);
+    }
+    if style.hide_from_taskbar {
+        ex_style = (ex_style | WS_EX_TOOLWINDOW.0 as isize) & !(WS_EX_APPWINDOW.0 as isize);
+    }
+    if style.click_through {
+        ex_style |= (WS_EX_LAYERED | WS_EX_TRANSPARENT).0 as isize;
+    }
+    if style.always_on_top {
+        ex_style |= WS_EX_TOPMOST.0 as isize;
+    } else {
+        ex_style &= !(WS_EX_TOPMOST.0 as isize);
+    }
+    set_styles(hwnd, window_style, ex_style);
+}
+
+// Put back the styles every window had before the tool changed them
+pub fn restore_window_styles() {
+    let styles = std::mem::take(&mut *APP.original_styles.lock().unwrap());
+    for (hwnd, (window_style, ex_style)) in styles {
+        if unsafe { IsWindow(Some(hwnd.0)) }.as_bool() {
+            set_styles(hwnd.0, window_style, ex_style);
+        }
+    }
+}
+
+fn set_styles(hwnd: HWND, window_style: isize, ex_style: isize) {
+    unsafe {
+        let current_style = GetWindowLongPtrW(hwnd, GWL_STYLE);
+        let current_ex_style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+        // Placement happens often, only redraw the frame when something changes
+        if current_style == window_style && current_ex_style == ex_style {
+            return;
+        }
+        SetWindowLongPtrW(hwnd, GWL_STYLE, window_style);
+        // The topmost bit can't be set directly, SetWindowPos below does that
+        SetWindowLongPtrW(hwnd, GWL_EXSTYLE, ex_style & !(WS_EX_TOPMOST.0 as isize));
+        if ex_style & WS_EX_LAYERED.0 as isize != 0 {
+            // Layered windows stay invisible until they are given an opacity
+            let _ = SetLayeredWindowAttributes(hwnd, COLORREF(0), 255, LWA_ALPHA);
+        }
+        // The taskbar only notices a changed tool window bit when the window is shown again
+        let taskbar_bits = (WS_EX_TOOLWINDOW | WS_EX_APPWINDOW).0 as isize;
+        if current_ex_style & taskbar_bits != ex_style & taskbar_bits {
+            let _ = ShowWindow(hwnd, SW_HIDE);
+            let _ = ShowWindow(hwnd, SW_SHOWNOACTIVATE);
+        }
+        let insert_after = if ex_style & WS_EX_TOPMOST.0 as isize != 0 {
+            HWND_TOPMOST
+        } else {
+            HWND_NOTOPMOST
+        };
+        let _ = SetWindowPos(
+            hwnd,
+            Some(insert_after),
+            0,
+            0,
+            0,
+            0,
+            SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE | SWP_FRAMECHANGED,
+        );
+    }
+}